@@ -1,34 +1,166 @@
-use log::info;
+mod anonymous;
+pub(crate) mod passwd;
+mod userfile;
 
-// TODO Expand this to allow for user authentication.
-pub fn validate(username: &str, _password: &str) -> bool {
-    if username == "anonymous" {
-        info!("Login succeeded for user {}", username);
-        return true;
+use std::path::PathBuf;
+
+use lazy_static::lazy_static;
+use log::{error, info};
+
+use crate::config;
+
+use self::{
+    anonymous::AnonymousAuthenticator,
+    userfile::{plain_verifier, UserFileAuthenticator},
+};
+
+/// What a session is allowed to do once logged in.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Permissions {
+    pub(crate) read: bool,
+    pub(crate) write: bool,
+    /// May point PORT and EPRT at other hosts, for server to server transfers.
+    pub(crate) fxp: bool,
+}
+
+impl Permissions {
+    /// Parses a flag string such as `rw` or `rwf`, unknown flags are ignored.
+    pub(crate) fn from_flags(flags: &str) -> Permissions {
+        Permissions {
+            read: flags.contains('r'),
+            write: flags.contains('w'),
+            fxp: flags.contains('f'),
+        }
+    }
+}
+
+/// A logged in user, as returned by an `Authenticator`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Identity {
+    pub(crate) display_name: String,
+    pub(crate) home: PathBuf,
+    pub(crate) permissions: Permissions,
+}
+
+pub(crate) trait Authenticator: Send + Sync {
+    /// Returns the identity of the user if the credentials are valid.
+    fn authenticate(&self, username: &str, password: &str) -> Option<Identity>;
+}
+
+/// Tries each backend in order, the first one to accept the user wins.
+impl Authenticator for Vec<Box<dyn Authenticator>> {
+    fn authenticate(&self, username: &str, password: &str) -> Option<Identity> {
+        self.iter()
+            .find_map(|backend| backend.authenticate(username, password))
+    }
+}
+
+lazy_static! {
+    static ref AUTHENTICATOR: Vec<Box<dyn Authenticator>> = create_authenticator();
+}
+
+/// The authenticator configured for this server.
+pub(crate) fn authenticator() -> &'static dyn Authenticator {
+    &*AUTHENTICATOR
+}
+
+/// Authenticates using the configured backends and logs the outcome.
+pub(crate) fn login(
+    authenticator: &dyn Authenticator,
+    username: &str,
+    password: &str,
+) -> Option<Identity> {
+    let identity = authenticator.authenticate(username, password);
+
+    match identity {
+        Some(ref identity) => info!(
+            "Login succeeded for user {} ({})",
+            username, identity.display_name
+        ),
+        None => info!("Login failed for user {}", username),
     }
 
-    info!("Login failed for user {}", username);
-    return false;
+    identity
+}
+
+fn create_authenticator() -> Vec<Box<dyn Authenticator>> {
+    let mut backends: Vec<Box<dyn Authenticator>> = Vec::new();
+
+    if let Some(path) = config::passwd_file() {
+        match UserFileAuthenticator::load(&path, passwd::verify) {
+            Ok(backend) => backends.push(Box::new(backend)),
+            Err(e) => error!("Failed to load password file {}: {}", path.display(), e),
+        }
+    }
+
+    if let Some(path) = config::users_file() {
+        match UserFileAuthenticator::load(&path, plain_verifier) {
+            Ok(backend) => backends.push(Box::new(backend)),
+            Err(e) => error!("Failed to load users file {}: {}", path.display(), e),
+        }
+    }
+
+    if config::anonymous_enabled() {
+        backends.push(Box::new(AnonymousAuthenticator::new(
+            config::session_root_dir(),
+            Permissions::from_flags(&config::anonymous_flags()),
+        )));
+    }
+
+    backends
+}
+
+/// Compares two strings in time that only depends on their lengths.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    struct MockAuthenticator(&'static str);
+    impl Authenticator for MockAuthenticator {
+        fn authenticate(&self, username: &str, _password: &str) -> Option<Identity> {
+            match username == self.0 {
+                true => Some(Identity {
+                    display_name: self.0.to_string(),
+                    home: PathBuf::from("/"),
+                    permissions: Permissions::default(),
+                }),
+                false => None,
+            }
+        }
+    }
+
     #[test]
-    fn test_validate_empty_false() {
-        assert_eq!(validate("", ""), false);
+    fn permissions_from_flags() {
+        assert_eq!(Permissions::from_flags(""), Permissions::default());
+        let perms = Permissions::from_flags("rw");
+        assert!(perms.read && perms.write);
+        let perms = Permissions::from_flags("r");
+        assert!(perms.read && !perms.write && !perms.fxp);
+        let perms = Permissions::from_flags("rf");
+        assert!(perms.read && !perms.write && perms.fxp);
     }
 
     #[test]
-    fn test_validate_anonymous_true() {
-        assert_eq!(validate("anonymous", ""), true);
-        assert_eq!(validate("anonymous", "foobar"), true);
+    fn chain_first_match_wins() {
+        let chain: Vec<Box<dyn Authenticator>> = vec![
+            Box::new(MockAuthenticator("foo")),
+            Box::new(MockAuthenticator("bar")),
+        ];
+        assert_eq!(chain.authenticate("bar", "").unwrap().display_name, "bar");
+        assert!(chain.authenticate("baz", "").is_none());
+        assert!(login(&chain, "foo", "").is_some());
     }
 
     #[test]
-    fn test_validate_user_false() {
-        assert_eq!(validate("user", ""), false);
-        assert_eq!(validate("user", "foobar"), false);
+    fn compare_constant_time() {
+        assert!(constant_time_eq("foobar", "foobar"));
+        assert!(!constant_time_eq("foobar", "foobaz"));
+        assert!(!constant_time_eq("foo", "foobar"));
+        assert!(constant_time_eq("", ""));
     }
 }
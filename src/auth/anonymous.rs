@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use super::{Authenticator, Identity, Permissions};
+
+const ANONYMOUS_USERNAME: &str = "anonymous";
+
+/// Accepts the `anonymous` user with any password.
+pub(super) struct AnonymousAuthenticator {
+    home: PathBuf,
+    permissions: Permissions,
+}
+
+impl AnonymousAuthenticator {
+    pub(super) fn new(home: PathBuf, permissions: Permissions) -> Self {
+        Self { home, permissions }
+    }
+}
+
+impl Authenticator for AnonymousAuthenticator {
+    fn authenticate(&self, username: &str, _password: &str) -> Option<Identity> {
+        if username != ANONYMOUS_USERNAME {
+            return None;
+        }
+
+        Some(Identity {
+            display_name: ANONYMOUS_USERNAME.to_string(),
+            home: self.home.clone(),
+            permissions: self.permissions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anonymous_any_password() {
+        let auth = AnonymousAuthenticator::new(PathBuf::from("/srv"), Permissions::from_flags("r"));
+        for password in ["", "foobar"] {
+            let identity = auth.authenticate("anonymous", password).unwrap();
+            assert_eq!(identity.home, PathBuf::from("/srv"));
+            assert_eq!(identity.permissions, Permissions::from_flags("r"));
+        }
+    }
+
+    #[test]
+    fn other_users_rejected() {
+        let auth = AnonymousAuthenticator::new(PathBuf::from("/"), Permissions::default());
+        assert!(auth.authenticate("", "").is_none());
+        assert!(auth.authenticate("user", "").is_none());
+        assert!(auth.authenticate("user", "foobar").is_none());
+    }
+}
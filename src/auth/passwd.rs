@@ -0,0 +1,255 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    process,
+};
+
+use argon2::{
+    password_hash::{rand_core::OsRng, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use lazy_static::lazy_static;
+use log::warn;
+
+lazy_static! {
+    /// Verified against when there is no usable hash, so that a missing user
+    /// costs as much as a wrong password.
+    static ref DUMMY_HASH: String = hash_password("").unwrap();
+}
+
+/// Checks `password` against an argon2 (`$argon2...`) or bcrypt (`$2b$...`)
+/// hash from the password file.
+pub(super) fn verify(hash: &str, password: &str) -> bool {
+    if hash.starts_with("$argon2") {
+        return verify_argon2(hash, password);
+    }
+
+    if ["$2a$", "$2b$", "$2y$"].iter().any(|p| hash.starts_with(p)) {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+
+    if !hash.is_empty() {
+        warn!("Ignoring password hash in unsupported format");
+    }
+    verify_argon2(&DUMMY_HASH, password);
+    false
+}
+
+fn verify_argon2(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Hashes `password` with argon2id and a random salt.
+pub(crate) fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+/// Sets the password hash of `user` in the password file at `path`, adding
+/// the user if needed. Home and flags default to those already in the file.
+pub(crate) fn set_entry(
+    path: &Path,
+    user: &str,
+    hash: &str,
+    home: Option<&str>,
+    flags: Option<&str>,
+) -> io::Result<()> {
+    validate_fields(user, home, flags)?;
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error),
+    };
+
+    let contents = update_entry(&contents, user, hash, home, flags)?;
+    write_atomically(path, &contents)
+}
+
+/// Refuses values that would break the line format of the password file, or
+/// add lines of their own.
+fn validate_fields(user: &str, home: Option<&str>, flags: Option<&str>) -> io::Result<()> {
+    let invalid = |message: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    let breaks_line = |value: &str| value.contains([':', '\n', '\r']);
+
+    if user.is_empty() || breaks_line(user) {
+        return invalid("User names cannot be empty or contain ':' or line breaks");
+    }
+    if home.is_some_and(|home| home.is_empty() || breaks_line(home)) {
+        return invalid("Home directories cannot be empty or contain ':' or line breaks");
+    }
+    if flags.is_some_and(|flags| !flags.chars().all(|flag| "rwf".contains(flag))) {
+        return invalid("Flags can only be 'r', 'w' and 'f'");
+    }
+    Ok(())
+}
+
+fn update_entry(
+    contents: &str,
+    user: &str,
+    hash: &str,
+    home: Option<&str>,
+    flags: Option<&str>,
+) -> io::Result<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut found = false;
+
+    for line in contents.lines() {
+        let fields: Vec<&str> = line.splitn(5, ':').collect();
+        if line.trim_start().starts_with('#') || fields[0] != user || found {
+            lines.push(line.to_string());
+            continue;
+        }
+
+        found = true;
+        let mut entry = vec![
+            user,
+            hash,
+            home.or(fields.get(2).copied()).unwrap_or("/"),
+            flags.or(fields.get(3).copied()).unwrap_or(""),
+        ];
+        entry.extend(fields.get(4));
+        lines.push(entry.join(":"));
+    }
+
+    if !found {
+        let home = match home {
+            Some(home) => home,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "A home directory is required for new users",
+                ))
+            }
+        };
+        lines.push([user, hash, home, flags.unwrap_or("r")].join(":"));
+    }
+
+    Ok(lines.join("\n") + "\n")
+}
+
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, process::id()));
+
+    let result = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn verify_argon2_hash() {
+        let hash = hash_password("secret").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify(&hash, "secret"));
+        assert!(!verify(&hash, "hunter2"));
+    }
+
+    #[test]
+    fn verify_bcrypt_hash() {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        assert!(verify(&hash, "secret"));
+        assert!(!verify(&hash, "hunter2"));
+    }
+
+    #[test]
+    fn reject_unknown_formats() {
+        assert!(!verify("", ""));
+        assert!(!verify("secret", "secret"));
+        assert!(!verify("$argon2id$garbage", "secret"));
+        assert!(!verify("$2b$garbage", "secret"));
+    }
+
+    #[test]
+    fn update_existing_entry() {
+        let contents = "# comment\nalice:old:/srv/alice:rw:Alice Smith\nbob:old:/srv/bob:r\n";
+        let updated = update_entry(contents, "alice", "new", None, None).unwrap();
+        assert_eq!(
+            updated,
+            "# comment\nalice:new:/srv/alice:rw:Alice Smith\nbob:old:/srv/bob:r\n"
+        );
+
+        let updated = update_entry(contents, "bob", "new", Some("/home/bob"), Some("rw")).unwrap();
+        assert!(updated.ends_with("bob:new:/home/bob:rw\n"));
+    }
+
+    #[test]
+    fn add_new_entry() {
+        let updated = update_entry("", "carol", "new", Some("/srv/carol"), None).unwrap();
+        assert_eq!(updated, "carol:new:/srv/carol:r\n");
+        assert!(update_entry("", "carol", "new", None, None).is_err());
+    }
+
+    #[test]
+    fn set_entry_writes_private_file() {
+        let dir = test_dir("passwd", "private");
+        let path = dir.join("passwd");
+
+        set_entry(&path, "alice", "hash", Some("/srv/alice"), Some("rw")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "alice:hash:/srv/alice:rw\n"
+        );
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        assert!(set_entry(&path, "a:b", "hash", Some("/"), None).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_fields() {
+        let dir = test_dir("passwd", "invalid");
+        let path = dir.join("passwd");
+        let invalid = [
+            ("", Some("/srv"), None),
+            ("a:b", Some("/srv"), None),
+            ("a\nb", Some("/srv"), None),
+            ("alice", Some(""), None),
+            ("alice", Some("/srv:rwf"), None),
+            ("alice", Some("/srv\nmallory:x:/:rwf"), None),
+            ("alice", Some("/srv\r"), None),
+            ("alice", Some("/srv"), Some("rwx")),
+            ("alice", Some("/srv"), Some("r:w")),
+            ("alice", Some("/srv"), Some("r\n")),
+        ];
+        for (user, home, flags) in invalid {
+            let error = set_entry(&path, user, "hash", home, flags).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(!path.exists());
+
+        set_entry(&path, "alice", "hash", Some("/srv"), Some("")).unwrap();
+        set_entry(&path, "alice", "hash", None, Some("rwf")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "alice:hash:/srv:rwf\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
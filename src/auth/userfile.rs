@@ -0,0 +1,126 @@
+use std::{collections::HashMap, fs, io, path::Path, path::PathBuf};
+
+use log::warn;
+
+use super::{constant_time_eq, Authenticator, Identity, Permissions};
+
+/// Checks a password against the secret stored for a user.
+pub(super) type Verifier = fn(secret: &str, password: &str) -> bool;
+
+/// Users listed in a configuration file, one per line:
+///
+/// `name:secret:home:flags:display name`
+///
+/// The display name is optional. Blank lines and lines starting with `#` are
+/// ignored. How the secret is checked is up to the `Verifier`.
+pub(super) struct UserFileAuthenticator {
+    users: HashMap<String, (String, Identity)>,
+    verifier: Verifier,
+}
+
+/// Plain text secrets, compared in constant time.
+pub(super) fn plain_verifier(secret: &str, password: &str) -> bool {
+    constant_time_eq(secret, password)
+}
+
+impl UserFileAuthenticator {
+    pub(super) fn load(path: &Path, verifier: Verifier) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?, verifier))
+    }
+
+    pub(super) fn parse(contents: &str, verifier: Verifier) -> Self {
+        let mut users = HashMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.splitn(5, ':').collect();
+            if fields.len() < 4 || fields[0].is_empty() {
+                warn!("Ignoring malformed line {} in users file", number + 1);
+                continue;
+            }
+
+            let identity = Identity {
+                display_name: fields.get(4).unwrap_or(&fields[0]).to_string(),
+                home: PathBuf::from(fields[2]),
+                permissions: Permissions::from_flags(fields[3]),
+            };
+            users.insert(fields[0].to_string(), (fields[1].to_string(), identity));
+        }
+
+        Self { users, verifier }
+    }
+}
+
+impl Authenticator for UserFileAuthenticator {
+    fn authenticate(&self, username: &str, password: &str) -> Option<Identity> {
+        let (secret, identity) = match self.users.get(username) {
+            Some(user) => user,
+            None => {
+                // Spend the same effort on unknown users, so that they cannot
+                // be told apart by timing.
+                (self.verifier)("", password);
+                return None;
+            }
+        };
+
+        match (self.verifier)(secret, password) {
+            true => Some(identity.clone()),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::passwd;
+
+    const USERS: &str = concat!(
+        "# name:password:home:flags:display name\n",
+        "\n",
+        "alice:secret:/srv/alice:rw:Alice Smith\n",
+        "bob:hunter2:/srv/bob:r\n",
+        "broken line\n",
+    );
+
+    #[test]
+    fn valid_credentials() {
+        let auth = UserFileAuthenticator::parse(USERS, plain_verifier);
+        let identity = auth.authenticate("alice", "secret").unwrap();
+        assert_eq!(identity.display_name, "Alice Smith");
+        assert_eq!(identity.home, PathBuf::from("/srv/alice"));
+        assert_eq!(identity.permissions, Permissions::from_flags("rw"));
+
+        let identity = auth.authenticate("bob", "hunter2").unwrap();
+        assert_eq!(identity.display_name, "bob");
+        assert_eq!(identity.permissions, Permissions::from_flags("r"));
+    }
+
+    #[test]
+    fn invalid_credentials() {
+        let auth = UserFileAuthenticator::parse(USERS, plain_verifier);
+        assert!(auth.authenticate("alice", "hunter2").is_none());
+        assert!(auth.authenticate("alice", "").is_none());
+        assert!(auth.authenticate("carol", "secret").is_none());
+        assert!(auth.authenticate("broken line", "").is_none());
+    }
+
+    #[test]
+    fn hashed_credentials() {
+        let hash = passwd::hash_password("secret").unwrap();
+        let contents = format!("alice:{}:/srv/alice:rw\n", hash);
+        let auth = UserFileAuthenticator::parse(&contents, passwd::verify);
+        assert!(auth.authenticate("alice", "secret").is_some());
+        assert!(auth.authenticate("alice", hash.as_str()).is_none());
+        assert!(auth.authenticate("bob", "secret").is_none());
+    }
+
+    #[test]
+    fn load_missing_file() {
+        assert!(UserFileAuthenticator::load(Path::new("/lajsldf/users"), plain_verifier).is_err());
+    }
+}
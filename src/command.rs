@@ -1,5 +1,7 @@
 pub(crate) mod errors;
 mod executor;
+
+pub(crate) use executor::feat::write_feat_reply;
 pub(crate) mod verb;
 
 use lazy_static::lazy_static;
@@ -30,7 +32,10 @@ impl Command {
         let arg = extract_argument(&buffer, format!("{:?}", verb).len());
         let result = Command { verb, arg };
 
-        info!("Parsed command: {:?} {:?}", result.verb, result.arg);
+        match result.verb {
+            Verb::PASS => info!("Parsed command: {:?} \"****\"", result.verb),
+            _ => info!("Parsed command: {:?} {:?}", result.verb, result.arg),
+        }
 
         Ok(result)
     }
@@ -39,6 +44,25 @@ impl Command {
         &self,
         current_state: &SessionState,
     ) -> Result<executor::ExecutionResult, errors::ExecutionError> {
+        if self.verb.requires_login() && !current_state.is_authenticated {
+            return Ok(executor::ExecutionResult {
+                status: 530,
+                message: "Not logged in.".to_string(),
+                new_state: None,
+            });
+        }
+
+        let permissions = current_state.permissions();
+        if (self.verb.requires_read() && !permissions.read)
+            || (self.verb.requires_write() && !permissions.write)
+        {
+            return Ok(executor::ExecutionResult {
+                status: 550,
+                message: "Permission denied.".to_string(),
+                new_state: None,
+            });
+        }
+
         let executor = self.verb.executor();
         executor(current_state, self.arg.as_str())
     }
@@ -120,20 +144,24 @@ fn extract_argument(buffer: &[u8], verb_length: usize) -> String {
 
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
+
+    use crate::auth::{Identity, Permissions};
+
     use super::*;
 
     #[test]
     fn test_command_from_empty_buffer() {
         let com = "";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 
     #[test]
     fn test_command_from_valid_with_args() {
         let com = "USER anonymous\r\n";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.verb, Verb::USER);
         assert_eq!(result.arg, "anonymous");
@@ -143,13 +171,13 @@ mod tests {
     fn test_command_from_valid_without_args() {
         let com = "USER\r\n";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.verb, Verb::USER);
         assert_eq!(result.arg, "");
         let com = "USER \r\n";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.verb, Verb::USER);
         assert_eq!(result.arg, "");
@@ -159,13 +187,13 @@ mod tests {
     fn test_command_from_valid_without_crlf() {
         let com = "USER anonymous";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.verb, Verb::USER);
         assert_eq!(result.arg, "anonymous");
         let com = "USER ";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.verb, Verb::USER);
         assert_eq!(result.arg, "");
@@ -175,13 +203,13 @@ mod tests {
     fn test_command_from_valid_without_cr() {
         let com = "USER anonymous\n";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.verb, Verb::USER);
         assert_eq!(result.arg, "anonymous");
         let com = "USER\n";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.verb, Verb::USER);
         assert_eq!(result.arg, "");
@@ -191,7 +219,7 @@ mod tests {
     fn test_command_from_non_ascii() {
         let com = "USER ö\r\n";
         let result = parse(com.as_bytes());
-        assert_eq!(result.is_err(), true);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -231,6 +259,46 @@ mod tests {
         assert!(result.new_state.is_some());
         let new_state = result.new_state.unwrap();
         assert_eq!(new_state.user, Some("foo".to_string()));
-        assert_eq!(new_state.is_authenticated, false);
+        assert!(!new_state.is_authenticated);
+    }
+
+    #[test]
+    fn test_execute_requires_login() {
+        let mut state = SessionState::default();
+        for com in ["RETR foo\r\n", "CWD /\r\n", "LIST\r\n"] {
+            let result = parse(com.as_bytes()).unwrap().execute(&state).unwrap();
+            assert_eq!(result.status, 530);
+            assert_eq!(result.message, "Not logged in.");
+            assert!(result.new_state.is_none());
+        }
+
+        state.is_authenticated = true;
+        let result = parse(b"CWD /\r\n").unwrap().execute(&state).unwrap();
+        assert_eq!(result.status, 250);
+    }
+
+    #[test]
+    fn test_execute_requires_permissions() {
+        let mut state = SessionState::default();
+        state.log_in(Identity {
+            display_name: "foo".to_string(),
+            home: PathBuf::from("/"),
+            permissions: Permissions::from_flags("r"),
+        });
+
+        let result = parse(b"RETR /bin/sh\r\n").unwrap().execute(&state).unwrap();
+        assert_eq!(result.status, 150);
+        let result = parse(b"DELE /bin/sh\r\n").unwrap().execute(&state).unwrap();
+        assert_eq!(result.status, 550);
+        assert_eq!(result.message, "Permission denied.");
+
+        state.log_in(Identity {
+            display_name: "foo".to_string(),
+            home: PathBuf::from("/"),
+            permissions: Permissions::from_flags("w"),
+        });
+        let result = parse(b"RETR /bin/sh\r\n").unwrap().execute(&state).unwrap();
+        assert_eq!(result.status, 550);
+        assert_eq!(result.message, "Permission denied.");
     }
 }
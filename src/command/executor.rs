@@ -1,18 +1,26 @@
 pub(super) mod acct;
 pub(super) mod allo;
 pub(super) mod appe;
+pub(super) mod auth;
 pub(super) mod cdup;
 pub(super) mod cwd;
 pub(super) mod dele;
+pub(super) mod eprt;
+pub(super) mod epsv;
+pub(super) mod feat;
 pub(super) mod help;
 pub(super) mod list;
 pub(super) mod mkd;
 pub(super) mod mode;
 pub(super) mod nlst;
 pub(super) mod noop;
+pub(super) mod opts;
 pub(super) mod pass;
+mod passive;
 pub(super) mod pasv;
+pub(super) mod pbsz;
 pub(super) mod port;
+pub(super) mod prot;
 pub(super) mod pwd;
 pub(super) mod quit;
 pub(super) mod rest;
@@ -20,14 +28,18 @@ pub(super) mod retr;
 pub(super) mod rmd;
 pub(super) mod rnfr;
 pub(super) mod rnto;
+pub(super) mod size;
 pub(super) mod stat;
 pub(super) mod stor;
 pub(super) mod stou;
 pub(super) mod stru;
 pub(super) mod syst;
+mod transfer;
 pub(super) mod r#type;
 pub(super) mod user;
 
+use std::io::{self, ErrorKind};
+
 use crate::{session::sessionstate::SessionState, status::Status};
 
 use super::errors::ExecutionError;
@@ -41,3 +53,20 @@ pub(crate) struct ExecutionResult {
     pub(crate) message: String,
     pub(crate) new_state: Option<SessionState>,
 }
+
+/// Maps a file system error to a permanent (550) or transient (450) failure.
+pub(super) fn file_error_status(error: &io::Error) -> Status {
+    match error.kind() {
+        ErrorKind::NotFound
+        | ErrorKind::PermissionDenied
+        | ErrorKind::AlreadyExists
+        | ErrorKind::InvalidInput
+        | ErrorKind::InvalidFilename
+        | ErrorKind::NotADirectory
+        | ErrorKind::IsADirectory
+        | ErrorKind::DirectoryNotEmpty
+        | ErrorKind::ReadOnlyFilesystem
+        | ErrorKind::CrossesDevices => 550,
+        _ => 450,
+    }
+}
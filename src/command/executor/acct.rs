@@ -33,9 +33,11 @@ mod tests {
 
     #[test]
     fn acct_already_logged_in_202() {
-        let mut state = SessionState::default();
-        state.previous_command = Some(Verb::PASS);
-        state.is_authenticated = true;
+        let state = SessionState {
+            previous_command: Some(Verb::PASS),
+            is_authenticated: true,
+            ..Default::default()
+        };
 
         let result = acct_command_executor(&state, "").unwrap();
 
@@ -45,9 +47,11 @@ mod tests {
 
     #[test]
     fn acct_previous_command_not_pass_503() {
-        let mut state = SessionState::default();
-        state.previous_command = Some(Verb::USER);
-        state.is_authenticated = true;
+        let state = SessionState {
+            previous_command: Some(Verb::USER),
+            is_authenticated: true,
+            ..Default::default()
+        };
 
         let result = acct_command_executor(&state, "").unwrap();
 
@@ -57,9 +61,11 @@ mod tests {
 
     #[test]
     fn acct_previous_login_rejected_530() {
-        let mut state = SessionState::default();
-        state.previous_command = Some(Verb::PASS);
-        state.is_authenticated = false;
+        let state = SessionState {
+            previous_command: Some(Verb::PASS),
+            is_authenticated: false,
+            ..Default::default()
+        };
 
         let result = acct_command_executor(&state, "").unwrap();
 
@@ -1,14 +1,111 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
 
-use super::ExecutionResult;
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, status::Status};
+
+use super::{transfer, ExecutionResult};
 
 pub(crate) fn appe_command_executor(
-    _state: &SessionState,
-    _argument: &str,
+    state: &SessionState,
+    argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
+    let file = match transfer::upload_target(state, argument) {
+        Ok(file) => file,
+        Err((status, message)) => {
+            return Ok(ExecutionResult {
+                status,
+                message,
+                new_state: None,
+            })
+        }
+    };
+
+    let mut new_state = state.clone();
+    new_state.data_transfer_func = Some(data_transfer_func);
+    new_state.data_transfer_func_parameter = Some(file.to_str().unwrap().to_string());
+    new_state.data_transfer_translated = true;
+    new_state.data_transfer_upload = true;
     Ok(ExecutionResult {
-        status: 502,
-        message: "Command not implemented.".to_string(),
-        new_state: None,
+        status: 150,
+        message: "Opening data connection.".to_string(),
+        new_state: Some(new_state),
     })
 }
+
+fn data_transfer_func(
+    argument: &str,
+    _start_position: usize,
+    read_stream: Option<&mut dyn Read>,
+    _write_stream: Option<&mut dyn Write>,
+) -> (Status, String) {
+    let stream = match read_stream {
+        Some(stream) => stream,
+        None => return (425, "No data connection was established.".to_string()),
+    };
+
+    transfer::append_file(Path::new(argument), stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn return_data_handler() {
+        let dir = test_dir("appe", "handler");
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        let result = appe_command_executor(&state, "log.txt").unwrap();
+        assert_eq!(result.status, 150);
+        assert_eq!(result.message, "Opening data connection.");
+        let new_state = result.new_state.unwrap();
+        assert_eq!(
+            new_state.data_transfer_func.unwrap() as usize,
+            data_transfer_func as usize
+        );
+        assert_eq!(
+            new_state.data_transfer_func_parameter.unwrap(),
+            dir.join("log.txt").to_str().unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_target() {
+        let state = SessionState::default();
+        let result = appe_command_executor(&state, "").unwrap();
+        assert_eq!(result.status, 501);
+        let result = appe_command_executor(&state, "/usr").unwrap();
+        assert_eq!(result.status, 553);
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn handle_no_connection() {
+        let (status, msg) = data_transfer_func("", 0, None, None);
+        assert_eq!(status, 425);
+        assert_eq!(msg, "No data connection was established.");
+    }
+
+    #[test]
+    fn append_stream_to_file() {
+        let dir = test_dir("appe", "append");
+        let file = dir.join("log.txt");
+        for line in ["foo\n", "bar\n"] {
+            let (status, msg) =
+                data_transfer_func(file.to_str().unwrap(), 0, Some(&mut line.as_bytes()), None);
+            assert_eq!(status, 226);
+            assert_eq!(msg, "Transfer complete.");
+        }
+        assert_eq!(fs::read(&file).unwrap(), b"foo\nbar\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, tls};
+
+use super::ExecutionResult;
+
+pub(crate) fn auth_command_executor(
+    state: &SessionState,
+    argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    auth_command_executor_with_tls(state, argument, tls::server_config().is_some())
+}
+
+fn auth_command_executor_with_tls(
+    state: &SessionState,
+    argument: &str,
+    tls_available: bool,
+) -> Result<ExecutionResult, ExecutionError> {
+    let mechanism = argument.to_uppercase();
+
+    let (status, message) = match mechanism.as_str() {
+        "" => (501, "Security mechanism required."),
+        "TLS" | "TLS-C" | "SSL" if state.tls_active => (503, "TLS is already active."),
+        "TLS" | "TLS-C" | "SSL" if tls_available => (234, "Proceed with TLS negotiation."),
+        "TLS" | "TLS-C" | "SSL" => (431, "TLS is not available."),
+        _ => (504, "Security mechanism not understood."),
+    };
+
+    // The control connection is upgraded once the reply has been sent, and
+    // the session has to log in again over the secured connection.
+    let new_state = match status {
+        234 => {
+            let mut new_state = state.clone();
+            new_state.tls_pending = true;
+            new_state.user = None;
+            new_state.is_authenticated = false;
+            new_state.identity = None;
+            Some(new_state)
+        }
+        _ => None,
+    };
+
+    Ok(ExecutionResult {
+        status,
+        message: message.to_string(),
+        new_state,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_tls() {
+        let state = SessionState {
+            is_authenticated: true,
+            ..Default::default()
+        };
+        for arg in ["TLS", "tls", "TLS-C", "SSL"] {
+            let result = auth_command_executor_with_tls(&state, arg, true).unwrap();
+            assert_eq!(result.status, 234);
+            assert_eq!(result.message, "Proceed with TLS negotiation.");
+            let new_state = result.new_state.unwrap();
+            assert!(new_state.tls_pending);
+            assert!(!new_state.is_authenticated);
+        }
+    }
+
+    #[test]
+    fn tls_not_configured_431() {
+        let state = SessionState::default();
+        let result = auth_command_executor_with_tls(&state, "TLS", false).unwrap();
+        assert_eq!(result.status, 431);
+        assert_eq!(result.message, "TLS is not available.");
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn tls_already_active_503() {
+        let state = SessionState {
+            tls_active: true,
+            ..Default::default()
+        };
+        let result = auth_command_executor_with_tls(&state, "TLS", true).unwrap();
+        assert_eq!(result.status, 503);
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn unknown_mechanism() {
+        let state = SessionState::default();
+        let result = auth_command_executor_with_tls(&state, "", true).unwrap();
+        assert_eq!(result.status, 501);
+        let result = auth_command_executor_with_tls(&state, "KERBEROS_V4", true).unwrap();
+        assert_eq!(result.status, 504);
+        assert_eq!(result.message, "Security mechanism not understood.");
+        assert!(result.new_state.is_none());
+    }
+}
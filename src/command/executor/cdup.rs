@@ -24,8 +24,10 @@ mod tests {
 
     #[test]
     fn change_to_parent_dir() {
-        let mut state = SessionState::default();
-        state.name_prefix = PathBuf::from("/usr/bin");
+        let mut state = SessionState {
+            name_prefix: PathBuf::from("/usr/bin"),
+            ..Default::default()
+        };
         let result = cdup_command_executor(&state, "").unwrap();
         assert!(result.new_state.is_some());
         assert_eq!(result.status, 200);
@@ -42,8 +44,10 @@ mod tests {
 
     #[test]
     fn change_when_root() {
-        let mut state = SessionState::default();
-        state.name_prefix = PathBuf::from("/");
+        let state = SessionState {
+            name_prefix: PathBuf::from("/"),
+            ..Default::default()
+        };
         let result = cdup_command_executor(&state, "").unwrap();
         assert!(result.new_state.is_some());
         assert_eq!(result.status, 200);
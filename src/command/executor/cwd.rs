@@ -1,5 +1,3 @@
-use std::fs::canonicalize;
-
 use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
 
 use super::ExecutionResult;
@@ -8,14 +6,15 @@ pub(crate) fn cwd_command_executor(
     state: &SessionState,
     argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    let mut path = state.name_prefix.clone();
-    let decoded_argument = argument.replace('\0', "\n");
-    path.push(decoded_argument);
-
-    let result = match canonicalize(path) {
+    let result = match state.resolve_path(argument) {
+        Ok(realpath) if !realpath.is_dir() => ExecutionResult {
+            status: 550,
+            message: "Not a directory.".to_string(),
+            new_state: None,
+        },
         Ok(realpath) => {
             let mut new_state = state.clone();
-            new_state.name_prefix = realpath;
+            new_state.name_prefix = state.virtual_path(&realpath);
             ExecutionResult {
                 status: 250,
                 message: "Okay.".to_string(),
@@ -36,6 +35,8 @@ pub(crate) fn cwd_command_executor(
 mod tests {
     use std::path::PathBuf;
 
+    use crate::testing::test_dir;
+
     use super::*;
 
     #[test]
@@ -60,8 +61,10 @@ mod tests {
 
     #[test]
     fn change_dir() {
-        let mut state = SessionState::default();
-        state.name_prefix = PathBuf::from("/usr");
+        let mut state = SessionState {
+            name_prefix: PathBuf::from("/usr"),
+            ..Default::default()
+        };
         let result = cwd_command_executor(&state, "bin").unwrap();
         assert_eq!(
             result.new_state.unwrap_or_default().name_prefix,
@@ -79,6 +82,39 @@ mod tests {
         assert_eq!(result.message, "Okay.");
     }
 
+    #[test]
+    fn cannot_leave_root() {
+        let root = test_dir("cwd", "root");
+        std::fs::create_dir(root.join("pub")).unwrap();
+        let mut state = SessionState {
+            root: root.clone(),
+            ..SessionState::default()
+        };
+
+        let result = cwd_command_executor(&state, "pub").unwrap();
+        assert_eq!(result.status, 250);
+        state = result.new_state.unwrap();
+        assert_eq!(state.name_prefix, PathBuf::from("/pub"));
+
+        for arg in ["../..", "/", "/../../.."] {
+            let result = cwd_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 250);
+            assert_eq!(result.new_state.unwrap().name_prefix, PathBuf::from("/"));
+        }
+
+        let result = cwd_command_executor(&state, "/usr").unwrap();
+        assert_eq!(result.status, 550);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn file_is_not_a_directory() {
+        let result = cwd_command_executor(&SessionState::default(), "/bin/sh").unwrap();
+        assert_eq!(result.status, 550);
+        assert_eq!(result.message, "Not a directory.");
+        assert!(result.new_state.is_none());
+    }
+
     #[test]
     fn test_nonexisting_folder() {
         let state = SessionState::default();
@@ -0,0 +1,162 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, status::Status};
+
+use super::{port, ExecutionResult};
+
+pub(crate) fn eprt_command_executor(
+    state: &SessionState,
+    argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    if state.epsv_all {
+        return Ok(result(503, "EPSV ALL in effect, use EPSV."));
+    }
+
+    let address = match parse_address(argument) {
+        Ok(address) => address,
+        Err((status, message)) => return Ok(result(status, message)),
+    };
+
+    if let Err((status, message)) = port::check_target(state, address) {
+        return Ok(ExecutionResult {
+            status,
+            message,
+            new_state: None,
+        });
+    }
+
+    let mut new_state = state.clone();
+    new_state.port_ip = Some(address);
+
+    // Like PORT, an active pasv listener is dropped.
+    new_state.data_listener = None;
+
+    Ok(ExecutionResult {
+        status: 200,
+        message: "Okay.".to_string(),
+        new_state: Some(new_state),
+    })
+}
+
+/// Parses `<d><af><d><address><d><port><d>` as described in RFC 2428, where
+/// `<d>` is any delimiter character.
+fn parse_address(argument: &str) -> Result<SocketAddr, (Status, &'static str)> {
+    let delimiter = match argument.chars().next() {
+        Some(delimiter) if (33..=126).contains(&(delimiter as u32)) => delimiter,
+        _ => return Err((501, "Invalid argument.")),
+    };
+
+    let fields: Vec<&str> = argument.split(delimiter).collect();
+    if fields.len() != 5 || !fields[0].is_empty() || !fields[4].is_empty() {
+        return Err((501, "Invalid argument."));
+    }
+
+    let ip = match fields[1] {
+        "1" => fields[2].parse::<Ipv4Addr>().map(IpAddr::V4),
+        "2" => fields[2].parse::<Ipv6Addr>().map(IpAddr::V6),
+        _ => return Err((522, "Network protocol not supported, use (1,2).")),
+    };
+
+    match (ip, fields[3].parse::<u16>()) {
+        (Ok(ip), Ok(port)) if port != 0 => Ok(SocketAddr::new(ip, port)),
+        _ => Err((501, "Invalid argument.")),
+    }
+}
+
+fn result(status: Status, message: &str) -> ExecutionResult {
+    ExecutionResult {
+        status,
+        message: message.to_string(),
+        new_state: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    fn state_with_peer(peer: &str) -> SessionState {
+        SessionState {
+            peer_ip: peer.parse().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_ipv4() {
+        let state = state_with_peer("132.235.1.2");
+        let res = eprt_command_executor(&state, "|1|132.235.1.2|6275|").unwrap();
+        assert_eq!(res.status, 200);
+        assert_eq!(res.message, "Okay.");
+        assert_eq!(
+            res.new_state.unwrap().port_ip,
+            Some("132.235.1.2:6275".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_ipv6() {
+        let mut state = state_with_peer("1080::8:800:200C:417A");
+        state.data_listener = Some(TcpListener::bind("0.0.0.0:0").unwrap());
+        let res = eprt_command_executor(&state, "!2!1080::8:800:200C:417A!5282!").unwrap();
+        assert_eq!(res.status, 200);
+        let new_state = res.new_state.unwrap();
+        assert_eq!(
+            new_state.port_ip,
+            Some("[1080::8:800:200C:417A]:5282".parse().unwrap())
+        );
+        assert!(new_state.data_listener.is_none());
+    }
+
+    #[test]
+    fn invalid_format() {
+        let state = SessionState::default();
+        for arg in [
+            "",
+            "foobar",
+            "|1|132.235.1.2|6275",
+            "|1|132.235.1.2|6275||",
+            "|1|1080::8|6275|",
+            "|2|132.235.1.2|6275|",
+            "|1|132.235.1.2|70000|",
+            "|1|132.235.1.2|0|",
+            " 1 132.235.1.2 6275 ",
+        ] {
+            let res = eprt_command_executor(&state, arg).unwrap();
+            assert_eq!(res.status, 501, "{}", arg);
+            assert_eq!(res.message, "Invalid argument.");
+            assert!(res.new_state.is_none());
+        }
+    }
+
+    #[test]
+    fn unknown_protocol_522() {
+        let state = SessionState::default();
+        let res = eprt_command_executor(&state, "|3|132.235.1.2|6275|").unwrap();
+        assert_eq!(res.status, 522);
+        assert_eq!(res.message, "Network protocol not supported, use (1,2).");
+    }
+
+    #[test]
+    fn epsv_all_503() {
+        let state = SessionState {
+            epsv_all: true,
+            ..Default::default()
+        };
+        let res = eprt_command_executor(&state, "|1|132.235.1.2|6275|").unwrap();
+        assert_eq!(res.status, 503);
+        assert!(res.new_state.is_none());
+    }
+
+    #[test]
+    fn bounce_protection_504() {
+        let state = state_with_peer("132.235.1.2");
+        let res = eprt_command_executor(&state, "|1|132.235.1.2|21|").unwrap();
+        assert_eq!(res.status, 504);
+        let res = eprt_command_executor(&state, "|2|1080::8:800:200C:417A|5282|").unwrap();
+        assert_eq!(res.status, 504);
+        assert_eq!(res.message, "Refusing to connect to another host.");
+    }
+}
@@ -0,0 +1,139 @@
+use std::net::IpAddr;
+
+use crate::{command::errors::ExecutionError, config, session::sessionstate::SessionState};
+
+use super::{passive, ExecutionResult};
+
+pub(crate) fn epsv_command_executor(
+    state: &SessionState,
+    argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    let protocol_matches = match (argument, state.local_ip) {
+        ("", _) | ("1", IpAddr::V4(_)) | ("2", IpAddr::V6(_)) => true,
+        ("1", _) | ("2", _) => false,
+        (all, _) if all.eq_ignore_ascii_case("ALL") => {
+            let mut new_state = state.clone();
+            new_state.epsv_all = true;
+            return Ok(ExecutionResult {
+                status: 200,
+                message: "EPSV ALL ok.".to_string(),
+                new_state: Some(new_state),
+            });
+        }
+        _ => {
+            return Ok(ExecutionResult {
+                status: 501,
+                message: "Invalid argument.".to_string(),
+                new_state: None,
+            })
+        }
+    };
+
+    // The data connection always uses the protocol of the control connection.
+    if !protocol_matches {
+        let protocol = match state.local_ip {
+            IpAddr::V4(_) => 1,
+            IpAddr::V6(_) => 2,
+        };
+        return Ok(ExecutionResult {
+            status: 522,
+            message: format!("Network protocol not supported, use ({}).", protocol),
+            new_state: None,
+        });
+    }
+
+    let listener = match passive::bind_listener(state.local_ip, config::passive_ports()) {
+        Ok(listener) => listener,
+        Err(_) => {
+            return Ok(ExecutionResult {
+                status: 425,
+                message: "Can't open data connection.".to_string(),
+                new_state: None,
+            })
+        }
+    };
+    let port = listener.local_addr().unwrap().port();
+
+    let mut new_state = state.clone();
+    new_state.data_listener = Some(listener);
+    new_state.port_ip = None;
+
+    Ok(ExecutionResult {
+        status: 229,
+        message: format!("Entering Extended Passive Mode (|||{}|)", port),
+        new_state: Some(new_state),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+
+    use super::*;
+
+    #[test]
+    fn extended_passive_mode() {
+        let state = SessionState {
+            local_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            ..Default::default()
+        };
+        for arg in ["", "1"] {
+            let result = epsv_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 229);
+
+            let new_state = result.new_state.unwrap();
+            let port = new_state
+                .data_listener
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            assert_eq!(
+                result.message,
+                format!("Entering Extended Passive Mode (|||{}|)", port)
+            );
+        }
+    }
+
+    #[test]
+    fn extended_passive_mode_ipv6() {
+        let state = SessionState {
+            local_ip: IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ..Default::default()
+        };
+        if TcpListener::bind("[::1]:0").is_err() {
+            return; // No IPv6 on this host.
+        }
+        let result = epsv_command_executor(&state, "2").unwrap();
+        assert_eq!(result.status, 229);
+        let listener = result.new_state.unwrap().data_listener.unwrap();
+        assert!(listener.local_addr().unwrap().is_ipv6());
+    }
+
+    #[test]
+    fn wrong_protocol_522() {
+        let state = SessionState::default();
+        let result = epsv_command_executor(&state, "2").unwrap();
+        assert_eq!(result.status, 522);
+        assert_eq!(result.message, "Network protocol not supported, use (1).");
+        assert!(result.new_state.is_none());
+
+        let result = epsv_command_executor(&state, "3").unwrap();
+        assert_eq!(result.status, 501);
+    }
+
+    #[test]
+    fn epsv_all() {
+        let state = SessionState::default();
+        let result = epsv_command_executor(&state, "all").unwrap();
+        assert_eq!(result.status, 200);
+        assert_eq!(result.message, "EPSV ALL ok.");
+        let new_state = result.new_state.unwrap();
+        assert!(new_state.epsv_all);
+        assert!(new_state.data_listener.is_none());
+
+        let result = epsv_command_executor(&new_state, "").unwrap();
+        assert_eq!(result.status, 229);
+        assert!(result.new_state.unwrap().epsv_all);
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+
+use crate::{
+    command::errors::ExecutionError, session::sessionstate::SessionState, status::Status, tls,
+};
+
+use super::ExecutionResult;
+
+const FEATURES: [&str; 5] = ["EPRT", "EPSV", "MODE Z", "REST STREAM", "SIZE"];
+const TLS_FEATURES: [&str; 3] = ["AUTH TLS", "PBSZ", "PROT"];
+
+pub(crate) fn feat_command_executor(
+    _state: &SessionState,
+    _argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    Ok(ExecutionResult {
+        status: 211,
+        message: features_message(tls::server_config().is_some()),
+        new_state: None,
+    })
+}
+
+fn features_message(tls_available: bool) -> String {
+    let tls_features: &[&str] = match tls_available {
+        true => &TLS_FEATURES,
+        false => &[],
+    };
+
+    let features = FEATURES
+        .iter()
+        .chain(tls_features)
+        .fold(String::new(), |acc, feature| {
+            format!("{} {}\n", acc, feature)
+        });
+
+    format!("Extensions supported:\n{}End", features)
+}
+
+/// Writes the reply to FEAT (RFC 2389). Unlike other multiline replies, the
+/// lines between the first and the last one carry no reply code, so that
+/// each feature is on a line of its own, indented by a space.
+pub(crate) fn write_feat_reply(
+    out: &mut dyn Write,
+    status: Status,
+    message: &str,
+) -> io::Result<usize> {
+    if message.contains('\r') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Message contains newline characters",
+        ));
+    }
+
+    let lines: Vec<&str> = message.split('\n').collect();
+    let last = lines.len() - 1;
+    let reply: String = lines
+        .iter()
+        .enumerate()
+        .map(|(idx, line)| match idx {
+            _ if idx == last => format!("{} {}\r\n", status, line),
+            0 => format!("{}-{}\r\n", status, line),
+            _ => format!("{}\r\n", line),
+        })
+        .collect();
+
+    out.write_all(reply.as_bytes())?;
+    Ok(reply.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_features() {
+        let res = feat_command_executor(&SessionState::default(), "").unwrap();
+        assert_eq!(res.status, 211);
+        assert_eq!(
+            res.message,
+            "Extensions supported:\n EPRT\n EPSV\n MODE Z\n REST STREAM\n SIZE\nEnd"
+        );
+        assert!(res.new_state.is_none());
+    }
+
+    #[test]
+    fn write_reply() {
+        let mut out = vec![];
+        let written = write_feat_reply(&mut out, 211, &features_message(false)).unwrap();
+        assert_eq!(written, out.len());
+        assert_eq!(
+            out,
+            b"211-Extensions supported:\r\n EPRT\r\n EPSV\r\n MODE Z\r\n REST STREAM\r\n SIZE\r\n211 End\r\n"
+        );
+
+        let mut out = vec![];
+        write_feat_reply(&mut out, 530, "Not logged in.").unwrap();
+        assert_eq!(out, b"530 Not logged in.\r\n");
+        assert!(write_feat_reply(&mut vec![], 211, "foo\r\nbar").is_err());
+    }
+
+    #[test]
+    fn list_tls_features() {
+        assert_eq!(
+            features_message(true),
+            "Extensions supported:\n EPRT\n EPSV\n MODE Z\n REST STREAM\n SIZE\n AUTH TLS\n PBSZ\n PROT\nEnd"
+        );
+    }
+}
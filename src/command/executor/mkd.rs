@@ -1,14 +1,113 @@
+use std::fs::create_dir;
+
 use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
 
-use super::ExecutionResult;
+use super::{file_error_status, ExecutionResult};
 
 pub(crate) fn mkd_command_executor(
-    _state: &SessionState,
-    _argument: &str,
+    state: &SessionState,
+    argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    Ok(ExecutionResult {
-        status: 502,
-        message: "Command not implemented.".to_string(),
-        new_state: None,
-    })
+    if argument.is_empty() {
+        return Ok(ExecutionResult {
+            status: 501,
+            message: "Directory name required.".to_string(),
+            new_state: None,
+        });
+    }
+
+    let path = state
+        .resolve_entry(argument)
+        .and_then(|path| create_dir(&path).map(|_| path));
+
+    let result = match path {
+        Ok(path) => {
+            let path = state.virtual_path(&path);
+            let path_encoded = path.to_str().unwrap().replace('\n', "\0");
+            ExecutionResult {
+                status: 257,
+                message: format!("\"{}\" created.", path_encoded.replace('"', "\"\"")),
+                new_state: None,
+            }
+        }
+        Err(error) => ExecutionResult {
+            status: file_error_status(&error),
+            message: error.to_string(),
+            new_state: None,
+        },
+    };
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn create_relative() {
+        let dir = test_dir("mkd", "relative");
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        let result = mkd_command_executor(&state, "foo").unwrap();
+        assert_eq!(result.status, 257);
+        assert_eq!(
+            result.message,
+            format!("\"{}\" created.", dir.join("foo").to_str().unwrap())
+        );
+        assert!(result.new_state.is_none());
+        assert!(dir.join("foo").is_dir());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn encode_special_characters() {
+        let dir = test_dir("mkd", "encode");
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        let result = mkd_command_executor(&state, "foo\0\"bar\"").unwrap();
+        assert_eq!(result.status, 257);
+        assert_eq!(
+            result.message,
+            format!("\"{}/foo\0\"\"bar\"\"\" created.", dir.to_str().unwrap())
+        );
+        assert!(dir.join("foo\n\"bar\"").is_dir());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn already_exists_550() {
+        let dir = test_dir("mkd", "exists");
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        let _ = mkd_command_executor(&state, "foo").unwrap();
+        let result = mkd_command_executor(&state, "foo").unwrap();
+        assert_eq!(result.status, 550);
+        assert_ne!(result.message, "");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_parent_550() {
+        let result = mkd_command_executor(&SessionState::default(), "/lajsldf/lskdfj").unwrap();
+        assert_eq!(result.status, 550);
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn missing_argument_501() {
+        let result = mkd_command_executor(&SessionState::default(), "").unwrap();
+        assert_eq!(result.status, 501);
+        assert_eq!(result.message, "Directory name required.");
+    }
 }
@@ -1,21 +1,34 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use crate::{
+    command::errors::ExecutionError,
+    session::sessionstate::{FileStructure, SessionState, TransferMode},
+};
 
 use super::ExecutionResult;
 
 pub(crate) fn mode_command_executor(
-    _state: &SessionState,
+    state: &SessionState,
     argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    let (status, message) = match argument {
-        "S" | "s" => (200, "Using stream mode."),
-        "" => (504, "Parameter required."),
-        _ => (504, "Only stream mode is supported."),
+    let (status, message, mode) = match argument {
+        "S" | "s" => (200, "Using stream mode.", Some(TransferMode::Stream)),
+        "B" | "b" if state.structure == FileStructure::Record => {
+            (504, "Block mode not supported with record structure.", None)
+        }
+        "B" | "b" => (200, "Using block mode.", Some(TransferMode::Block)),
+        "Z" | "z" => (200, "Using deflate mode.", Some(TransferMode::Deflate)),
+        "" => (504, "Parameter required.", None),
+        _ => (504, "Unsupported transfer mode.", None),
     };
 
+    let new_state = mode.map(|mode| SessionState {
+        transfer_mode: mode,
+        ..state.clone()
+    });
+
     Ok(ExecutionResult {
         status,
         message: message.to_string(),
-        new_state: None,
+        new_state,
     })
 }
 
@@ -29,7 +42,44 @@ mod tests {
         for arg in ["S", "s"] {
             let result = mode_command_executor(&state, arg).unwrap();
             assert_eq!(result.status, 200);
-            assert_eq!(result.message, "Using stream mode.")
+            assert_eq!(result.message, "Using stream mode.");
+            assert_eq!(
+                result.new_state.unwrap().transfer_mode,
+                TransferMode::Stream
+            );
+        }
+    }
+
+    #[test]
+    fn accept_block() {
+        let state = SessionState::default();
+        for arg in ["B", "b"] {
+            let result = mode_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 200);
+            assert_eq!(result.message, "Using block mode.");
+            assert_eq!(result.new_state.unwrap().transfer_mode, TransferMode::Block);
+        }
+
+        let state = SessionState {
+            structure: FileStructure::Record,
+            ..state
+        };
+        let result = mode_command_executor(&state, "B").unwrap();
+        assert_eq!(result.status, 504);
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn accept_deflate() {
+        let state = SessionState::default();
+        for arg in ["Z", "z"] {
+            let result = mode_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 200);
+            assert_eq!(result.message, "Using deflate mode.");
+            assert_eq!(
+                result.new_state.unwrap().transfer_mode,
+                TransferMode::Deflate
+            );
         }
     }
 
@@ -41,6 +91,7 @@ mod tests {
         assert_eq!(result.message, "Parameter required.");
         let result = mode_command_executor(&state, "foobar").unwrap();
         assert_eq!(result.status, 504);
-        assert_eq!(result.message, "Only stream mode is supported.");
+        assert_eq!(result.message, "Unsupported transfer mode.");
+        assert!(result.new_state.is_none());
     }
 }
@@ -16,21 +16,10 @@ pub(crate) fn nlst_command_executor(
     state: &SessionState,
     argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    let mut path = state.name_prefix.to_owned();
+    let path = state.resolve_path(argument);
 
-    if !argument.is_empty() {
-        path = path.join(argument);
-    }
-
-    let exists = path.exists();
-
-    match exists {
-        false => Ok(ExecutionResult {
-            status: 550,
-            message: "File not found.".to_string(),
-            new_state: None,
-        }),
-        true => {
+    match path {
+        Ok(path) if path.exists() => {
             let mut new_state = state.clone();
             new_state.data_transfer_func = Some(data_transfer_func);
             new_state.data_transfer_func_parameter = Some(path.to_str().unwrap().to_string());
@@ -40,6 +29,11 @@ pub(crate) fn nlst_command_executor(
                 new_state: Some(new_state),
             })
         }
+        _ => Ok(ExecutionResult {
+            status: 550,
+            message: "File not found.".to_string(),
+            new_state: None,
+        }),
     }
 }
 
@@ -0,0 +1,71 @@
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+
+use super::ExecutionResult;
+
+const MAX_DEFLATE_LEVEL: u32 = 9;
+
+/// Sets options of other commands (RFC 2389). Only the compression level of
+/// deflate mode can be set, with `OPTS MODE Z LEVEL <0-9>`.
+pub(crate) fn opts_command_executor(
+    state: &SessionState,
+    argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    let upper = argument.to_ascii_uppercase();
+    let words: Vec<&str> = upper.split_whitespace().collect();
+
+    let (status, message, new_state) = match words.as_slice() {
+        ["MODE", "Z", "LEVEL", level] => match level.parse::<u32>() {
+            Ok(level) if level <= MAX_DEFLATE_LEVEL => {
+                let mut new_state = state.clone();
+                new_state.deflate_level = level;
+                (
+                    200,
+                    format!("MODE Z LEVEL set to {}.", level),
+                    Some(new_state),
+                )
+            }
+            _ => (501, "Invalid compression level.".to_string(), None),
+        },
+        [] => (501, "Option required.".to_string(), None),
+        _ => (501, "Option not understood.".to_string(), None),
+    };
+
+    Ok(ExecutionResult {
+        status,
+        message,
+        new_state,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_deflate_level() {
+        let state = SessionState::default();
+        for (arg, level) in [("MODE Z LEVEL 9", 9), ("mode z level 0", 0)] {
+            let result = opts_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 200);
+            assert_eq!(result.message, format!("MODE Z LEVEL set to {}.", level));
+            assert_eq!(result.new_state.unwrap().deflate_level, level);
+        }
+    }
+
+    #[test]
+    fn reject_invalid_options() {
+        let state = SessionState::default();
+        for arg in ["MODE Z LEVEL 10", "MODE Z LEVEL -1", "MODE Z LEVEL x"] {
+            let result = opts_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 501);
+            assert_eq!(result.message, "Invalid compression level.");
+            assert!(result.new_state.is_none());
+        }
+        let result = opts_command_executor(&state, "UTF8 ON").unwrap();
+        assert_eq!(result.status, 501);
+        assert_eq!(result.message, "Option not understood.");
+        let result = opts_command_executor(&state, "").unwrap();
+        assert_eq!(result.status, 501);
+        assert_eq!(result.message, "Option required.");
+    }
+}
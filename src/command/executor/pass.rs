@@ -1,5 +1,5 @@
 use crate::{
-    auth,
+    auth::{self, Authenticator},
     command::{errors::ExecutionError, verb::Verb},
     session::sessionstate::SessionState,
 };
@@ -10,13 +10,13 @@ pub(crate) fn pass_command_executor(
     state: &SessionState,
     argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    pass_command_executor_with_validator(&state, argument, auth::validate)
+    pass_command_executor_with_authenticator(&state, argument, auth::authenticator())
 }
 
-fn pass_command_executor_with_validator(
+fn pass_command_executor_with_authenticator(
     state: &SessionState,
     argument: &str,
-    validator: fn(&str, &str) -> bool,
+    authenticator: &dyn Authenticator,
 ) -> Result<ExecutionResult, ExecutionError> {
     let mut result = ExecutionResult::default();
 
@@ -29,12 +29,14 @@ fn pass_command_executor_with_validator(
     } else if let "" = argument {
         result.status = 501;
         result.message.push_str("Password parameter empty.")
-    } else if validator(&state.user.as_ref().unwrap(), argument) {
+    } else if let Some(identity) =
+        auth::login(authenticator, state.user.as_ref().unwrap(), argument)
+    {
         result.status = 230;
         result.message.push_str("User logged in, proceed.");
 
         let mut new_state = state.clone();
-        new_state.is_authenticated = true;
+        new_state.log_in(identity);
         result.new_state = Some(new_state);
     } else {
         result.status = 530;
@@ -50,18 +52,39 @@ fn is_anonymous_user(state: &SessionState) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::command::verb::Verb;
+    use std::path::PathBuf;
+
+    use crate::{
+        auth::{Identity, Permissions},
+        command::verb::Verb,
+    };
 
     use super::*;
 
+    struct MockAuthenticator {}
+    impl Authenticator for MockAuthenticator {
+        fn authenticate(&self, username: &str, password: &str) -> Option<Identity> {
+            match username == "foo" && password == "bar" {
+                true => Some(Identity {
+                    display_name: "Foo".to_string(),
+                    home: PathBuf::from("/usr"),
+                    permissions: Permissions::from_flags("rw"),
+                }),
+                false => None,
+            }
+        }
+    }
+
     #[test]
     fn pass_valid_credentials_returns_230() {
-        let validator = |u: &str, p: &str| u == "foo" && p == "bar";
-        let mut state = SessionState::default();
-        state.user = Some("foo".to_string());
-        state.previous_command = Some(Verb::USER);
+        let state = SessionState {
+            user: Some("foo".to_string()),
+            previous_command: Some(Verb::USER),
+            ..Default::default()
+        };
         let argument = "bar";
-        let result = pass_command_executor_with_validator(&state, argument, validator);
+        let result =
+            pass_command_executor_with_authenticator(&state, argument, &MockAuthenticator {});
         assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.status, 230);
@@ -70,13 +93,18 @@ mod tests {
         let new_state = result.new_state.unwrap();
         assert_eq!(new_state.user, Some("foo".to_string()));
         assert!(new_state.is_authenticated);
+        assert_eq!(new_state.root, PathBuf::from("/usr"));
+        assert_eq!(new_state.name_prefix, PathBuf::from("/"));
+        assert_eq!(new_state.permissions(), Permissions::from_flags("rw"));
     }
 
     #[test]
     fn pass_no_argument_returns_501() {
-        let mut state = SessionState::default();
-        state.user = Some("foo".to_string());
-        state.previous_command = Some(Verb::USER);
+        let state = SessionState {
+            user: Some("foo".to_string()),
+            previous_command: Some(Verb::USER),
+            ..Default::default()
+        };
         let argument = "";
         let result = pass_command_executor(&state, argument);
         assert!(result.is_ok());
@@ -88,10 +116,12 @@ mod tests {
 
     #[test]
     fn pass_anonymous_202() {
-        let mut state = SessionState::default();
-        state.user = Some("anonymous".to_string());
-        state.previous_command = Some(Verb::USER);
-        state.is_authenticated = true;
+        let state = SessionState {
+            user: Some("anonymous".to_string()),
+            previous_command: Some(Verb::USER),
+            is_authenticated: true,
+            ..Default::default()
+        };
         let argument = "";
         let result = pass_command_executor(&state, argument);
         assert!(result.is_ok());
@@ -103,12 +133,14 @@ mod tests {
 
     #[test]
     fn pass_invalid_credentials_530() {
-        let validator = |u: &str, p: &str| u == "foo" && p == "bar";
-        let mut state = SessionState::default();
-        state.user = Some("foo".to_string());
-        state.previous_command = Some(Verb::USER);
+        let state = SessionState {
+            user: Some("foo".to_string()),
+            previous_command: Some(Verb::USER),
+            ..Default::default()
+        };
         let argument = "baz";
-        let result = pass_command_executor_with_validator(&state, argument, validator);
+        let result =
+            pass_command_executor_with_authenticator(&state, argument, &MockAuthenticator {});
         assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.status, 530);
@@ -118,12 +150,14 @@ mod tests {
 
     #[test]
     fn pass_last_command_must_be_user_503() {
-        let validator = |u: &str, p: &str| u == "foo" && p == "bar";
-        let mut state = SessionState::default();
-        state.user = Some("foo".to_string());
-        state.previous_command = Some(Verb::ACCT);
+        let state = SessionState {
+            user: Some("foo".to_string()),
+            previous_command: Some(Verb::ACCT),
+            ..Default::default()
+        };
         let argument = "bar";
-        let result = pass_command_executor_with_validator(&state, argument, validator);
+        let result =
+            pass_command_executor_with_authenticator(&state, argument, &MockAuthenticator {});
         assert!(result.is_ok());
         let result = result.unwrap();
         assert_eq!(result.status, 503);
@@ -0,0 +1,103 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, ToSocketAddrs},
+    ops::RangeInclusive,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::warn;
+
+static NEXT_PORT: AtomicUsize = AtomicUsize::new(0);
+
+/// Binds a passive data listener on `ip`. With a port range, the search starts
+/// where the previous one left off so that concurrent sessions spread out.
+pub(super) fn bind_listener(
+    ip: IpAddr,
+    ports: Option<RangeInclusive<u16>>,
+) -> io::Result<TcpListener> {
+    let ports = match ports {
+        Some(ports) => ports,
+        None => return TcpListener::bind(SocketAddr::new(ip, 0)),
+    };
+
+    let count = ports.len();
+    let start = NEXT_PORT.fetch_add(1, Ordering::Relaxed);
+
+    for offset in 0..count {
+        let port = *ports.start() + ((start + offset) % count) as u16;
+        match TcpListener::bind(SocketAddr::new(ip, port)) {
+            Ok(listener) => return Ok(listener),
+            Err(error) if error.kind() == ErrorKind::AddrInUse => continue,
+            Err(error) => return Err(error),
+        }
+    }
+
+    Err(io::Error::new(
+        ErrorKind::AddrInUse,
+        "No free port in the passive range",
+    ))
+}
+
+/// The address to send in a 227 reply: the configured address or host name
+/// if there is one, the local address of the control connection otherwise.
+pub(super) fn advertised_address(local_ip: Ipv4Addr, configured: Option<&str>) -> Ipv4Addr {
+    let configured = match configured {
+        Some(configured) => configured,
+        None => return local_ip,
+    };
+
+    let resolved = (configured, 0).to_socket_addrs().map(|mut addresses| {
+        addresses.find_map(|address| match address.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+    });
+
+    match resolved {
+        Ok(Some(ip)) => ip,
+        _ => {
+            warn!("Passive address {} has no IPv4 address", configured);
+            local_ip
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_any_port() {
+        let listener = bind_listener(IpAddr::V4(Ipv4Addr::LOCALHOST), None).unwrap();
+        assert_ne!(listener.local_addr().unwrap().port(), 0);
+    }
+
+    #[test]
+    fn bind_in_range() {
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let range = port..=port;
+        assert!(bind_listener(ip, Some(range.clone())).is_err());
+        drop(taken);
+        let listener = bind_listener(ip, Some(range)).unwrap();
+        assert_eq!(listener.local_addr().unwrap().port(), port);
+    }
+
+    #[test]
+    fn advertise_configured_address() {
+        let local = Ipv4Addr::new(10, 0, 0, 1);
+        assert_eq!(advertised_address(local, None), local);
+        assert_eq!(
+            advertised_address(local, Some("203.0.113.7")),
+            Ipv4Addr::new(203, 0, 113, 7)
+        );
+        assert_eq!(
+            advertised_address(local, Some("localhost")),
+            Ipv4Addr::LOCALHOST
+        );
+        assert_eq!(advertised_address(local, Some("::1")), local);
+        assert_eq!(advertised_address(local, Some("lajsldf.invalid")), local);
+    }
+}
@@ -1,22 +1,35 @@
-use std::net::TcpListener;
+use std::net::{IpAddr, Ipv4Addr};
 
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use crate::{
+    command::errors::ExecutionError, config, session::sessionstate::SessionState, status::Status,
+};
 
-use super::ExecutionResult;
+use super::{passive, ExecutionResult};
 
 pub(crate) fn pasv_command_executor(
     state: &SessionState,
     _argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    let listener = TcpListener::bind("0.0.0.0:0").unwrap();
-    let ip = state.local_ip.octets();
+    let local_ip = match state.local_ip {
+        _ if state.epsv_all => return Ok(reject(503, "EPSV ALL in effect, use EPSV.")),
+        IpAddr::V4(ip) => ip,
+        IpAddr::V6(_) => return Ok(reject(502, "PASV is not available over IPv6, use EPSV.")),
+    };
+
+    let bind_ip = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+    let listener = match passive::bind_listener(bind_ip, config::passive_ports()) {
+        Ok(listener) => listener,
+        Err(_) => return Ok(reject(425, "Can't open data connection.")),
+    };
     let port = listener.local_addr().unwrap().port();
 
     let mut new_state = state.clone();
     new_state.data_listener = Some(listener);
 
+    let passive_address = config::passive_address();
+    let ip = passive::advertised_address(local_ip, passive_address.as_deref()).octets();
     let message = format!(
-        "={},{},{},{},{},{}",
+        "Entering Passive Mode ({},{},{},{},{},{}).",
         ip[0],
         ip[1],
         ip[2],
@@ -32,9 +45,17 @@ pub(crate) fn pasv_command_executor(
     })
 }
 
+fn reject(status: Status, message: &str) -> ExecutionResult {
+    ExecutionResult {
+        status,
+        message: message.to_string(),
+        new_state: None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::net::TcpListener;
+    use std::net::{Ipv6Addr, TcpListener};
 
     use super::*;
 
@@ -43,9 +64,12 @@ mod tests {
         let state = SessionState::default();
         let result = pasv_command_executor(&state, "").unwrap();
         assert_eq!(result.status, 227);
-        assert!(result.message.starts_with('='));
+        assert!(result.message.starts_with("Entering Passive Mode ("));
+        assert!(result.message.ends_with(")."));
 
-        let addr: Vec<&str> = result.message[1..].split(",").collect();
+        let addr: Vec<&str> = result.message[23..result.message.len() - 2]
+            .split(",")
+            .collect();
         assert_eq!(addr.len(), 6);
         for part in addr {
             assert!(part.parse::<u8>().is_ok());
@@ -56,8 +80,10 @@ mod tests {
 
     #[test]
     fn passive_mode_already_listening() {
-        let mut state = SessionState::default();
-        state.data_listener = Some(TcpListener::bind("127.0.0.1:29743").unwrap());
+        let state = SessionState {
+            data_listener: Some(TcpListener::bind("127.0.0.1:29743").unwrap()),
+            ..Default::default()
+        };
         let result = pasv_command_executor(&state, "").unwrap();
         assert!(result.new_state.is_some());
         let new_state = result.new_state.unwrap();
@@ -72,4 +98,27 @@ mod tests {
             29743
         );
     }
+
+    #[test]
+    fn ipv6_session_502() {
+        let state = SessionState {
+            local_ip: IpAddr::V6(Ipv6Addr::LOCALHOST),
+            ..Default::default()
+        };
+        let result = pasv_command_executor(&state, "").unwrap();
+        assert_eq!(result.status, 502);
+        assert_eq!(result.message, "PASV is not available over IPv6, use EPSV.");
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn epsv_all_503() {
+        let state = SessionState {
+            epsv_all: true,
+            ..Default::default()
+        };
+        let result = pasv_command_executor(&state, "").unwrap();
+        assert_eq!(result.status, 503);
+        assert!(result.new_state.is_none());
+    }
 }
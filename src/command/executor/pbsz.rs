@@ -0,0 +1,65 @@
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+
+use super::ExecutionResult;
+
+pub(crate) fn pbsz_command_executor(
+    state: &SessionState,
+    argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    if !state.tls_active {
+        return Ok(ExecutionResult {
+            status: 503,
+            message: "Security data exchange not complete.".to_string(),
+            new_state: None,
+        });
+    }
+
+    if argument.parse::<u32>().is_err() {
+        return Ok(ExecutionResult {
+            status: 501,
+            message: "Invalid buffer size.".to_string(),
+            new_state: None,
+        });
+    }
+
+    // TLS does its own framing, so the only buffer size that makes sense is 0.
+    let mut new_state = state.clone();
+    new_state.pbsz_set = true;
+    Ok(ExecutionResult {
+        status: 200,
+        message: "PBSZ=0".to_string(),
+        new_state: Some(new_state),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_tls() {
+        let state = SessionState::default();
+        let result = pbsz_command_executor(&state, "0").unwrap();
+        assert_eq!(result.status, 503);
+        assert_eq!(result.message, "Security data exchange not complete.");
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn buffer_size_is_always_zero() {
+        let state = SessionState {
+            tls_active: true,
+            ..Default::default()
+        };
+        for arg in ["0", "1024"] {
+            let result = pbsz_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 200);
+            assert_eq!(result.message, "PBSZ=0");
+            assert!(result.new_state.unwrap().pbsz_set);
+        }
+
+        let result = pbsz_command_executor(&state, "foo").unwrap();
+        assert_eq!(result.status, 501);
+        assert!(result.new_state.is_none());
+    }
+}
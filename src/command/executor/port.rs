@@ -1,15 +1,26 @@
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::net::{Ipv4Addr, SocketAddr};
 
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use log::warn;
+
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, status::Status};
 
 use super::ExecutionResult;
 
 const BYTES_IN_IP_WITH_PORT: usize = 6;
+const FIRST_UNPRIVILEGED_PORT: u16 = 1024;
 
 pub(crate) fn port_command_executor(
     state: &SessionState,
     argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
+    if state.epsv_all {
+        return Ok(ExecutionResult {
+            status: 503,
+            message: "EPSV ALL in effect, use EPSV.".to_string(),
+            new_state: None,
+        });
+    }
+
     let bytes: Vec<u8> = argument
         .split(",")
         .map(|x| x.parse::<u8>())
@@ -24,7 +35,15 @@ pub(crate) fn port_command_executor(
     let ip = Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]);
     let port = ((bytes[4] as u16) << 8) | bytes[5] as u16;
 
-    let port_ip = SocketAddrV4::new(ip, port);
+    let port_ip = SocketAddr::from((ip, port));
+    if let Err((status, message)) = check_target(state, port_ip) {
+        return Ok(ExecutionResult {
+            status,
+            message,
+            new_state: None,
+        });
+    }
+
     let mut new_state = state.clone();
     new_state.port_ip = Some(port_ip);
 
@@ -39,6 +58,31 @@ pub(crate) fn port_command_executor(
     })
 }
 
+/// Refuses active mode targets that could be used to bounce connections off
+/// this server: privileged ports, and hosts other than the client. Users
+/// allowed to do server to server transfers are trusted with both.
+pub(super) fn check_target(
+    state: &SessionState,
+    target: SocketAddr,
+) -> Result<(), (Status, String)> {
+    let trusted = state.fxp_allowed(target.ip());
+    if target.port() < FIRST_UNPRIVILEGED_PORT && !trusted {
+        warn!("Refused active mode target {}, privileged port", target);
+        return Err((504, "Refusing to connect to a privileged port.".to_string()));
+    }
+
+    let foreign = target.ip().to_canonical() != state.peer_ip;
+    if foreign && !trusted {
+        warn!(
+            "Refused active mode target {}, control connection is from {}",
+            target, state.peer_ip
+        );
+        return Err((504, "Refusing to connect to another host.".to_string()));
+    }
+
+    Ok(())
+}
+
 fn invalid_format() -> ExecutionResult {
     ExecutionResult {
         status: 501,
@@ -49,22 +93,31 @@ fn invalid_format() -> ExecutionResult {
 
 #[cfg(test)]
 mod tests {
-    use std::net::{Ipv4Addr, TcpListener};
+    use std::net::{IpAddr, Ipv4Addr, TcpListener};
+
+    use crate::auth::{Identity, Permissions};
 
     use super::*;
 
+    fn local_state() -> SessionState {
+        SessionState {
+            peer_ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn parse_port() {
         let arg = "127,0,0,1,221,103";
-        let state = SessionState::default();
-        let res = port_command_executor(&state, &arg).unwrap();
+        let state = local_state();
+        let res = port_command_executor(&state, arg).unwrap();
         assert_eq!(res.status, 200);
         assert_eq!(res.message, "Okay.");
         assert!(res.new_state.is_some());
         let new_state = res.new_state.unwrap();
         assert!(new_state.port_ip.is_some());
         let portip = new_state.port_ip.unwrap();
-        assert_eq!(portip.ip(), &Ipv4Addr::new(127, 0, 0, 1));
+        assert_eq!(portip.ip(), IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
         assert_eq!(portip.port(), 221 * 256 + 103);
     }
 
@@ -78,7 +131,7 @@ mod tests {
             "-12,0,0,1,200,100",
             "256,0,0,1,200,100",
         ] {
-            let res = port_command_executor(&state, &arg).unwrap();
+            let res = port_command_executor(&state, arg).unwrap();
             assert_eq!(res.status, 501);
             assert_eq!(res.message, "Invalid argument.");
             assert!(res.new_state.is_none());
@@ -87,7 +140,7 @@ mod tests {
 
     #[test]
     fn shutsdown_passive_listener() {
-        let mut state = SessionState::default();
+        let mut state = local_state();
         state.data_listener = Some(TcpListener::bind("0.0.0.0:0").unwrap());
         let res = port_command_executor(&state, "127,0,0,1,100,200").unwrap();
         assert_eq!(res.status, 200);
@@ -97,4 +150,47 @@ mod tests {
         assert!(new_state.data_listener.is_none());
         assert!(new_state.port_ip.is_some());
     }
+
+    #[test]
+    fn epsv_all_503() {
+        let state = SessionState {
+            epsv_all: true,
+            ..Default::default()
+        };
+        let res = port_command_executor(&state, "127,0,0,1,100,200").unwrap();
+        assert_eq!(res.status, 503);
+        assert_eq!(res.message, "EPSV ALL in effect, use EPSV.");
+        assert!(res.new_state.is_none());
+    }
+
+    #[test]
+    fn privileged_port_504() {
+        let res = port_command_executor(&local_state(), "127,0,0,1,3,255").unwrap();
+        assert_eq!(res.status, 504);
+        assert_eq!(res.message, "Refusing to connect to a privileged port.");
+        assert!(res.new_state.is_none());
+    }
+
+    #[test]
+    fn foreign_host_504() {
+        let res = port_command_executor(&local_state(), "192,0,2,1,100,200").unwrap();
+        assert_eq!(res.status, 504);
+        assert_eq!(res.message, "Refusing to connect to another host.");
+        assert!(res.new_state.is_none());
+    }
+
+    #[test]
+    fn foreign_host_and_privileged_port_allowed_for_fxp_users() {
+        let mut state = local_state();
+        state.log_in(Identity {
+            display_name: "mirror".to_string(),
+            home: "/usr".into(),
+            permissions: Permissions::from_flags("rf"),
+        });
+        let res = port_command_executor(&state, "192,0,2,1,100,200").unwrap();
+        assert_eq!(res.status, 200);
+
+        let res = port_command_executor(&state, "192,0,2,1,0,21").unwrap();
+        assert_eq!(res.status, 200);
+    }
 }
@@ -0,0 +1,95 @@
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+
+use super::ExecutionResult;
+
+pub(crate) fn prot_command_executor(
+    state: &SessionState,
+    argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    let mut new_state = state.clone();
+
+    let (status, message) = match argument.to_uppercase().as_str() {
+        _ if !state.pbsz_set => (503, "PBSZ required first."),
+        "C" if state.implicit_tls => (534, "Data connections must be protected."),
+        "C" => {
+            new_state.protect_data = false;
+            (200, "Protection level set to Clear.")
+        }
+        "P" => {
+            new_state.protect_data = true;
+            (200, "Protection level set to Private.")
+        }
+        "S" | "E" => (536, "Protection level not supported."),
+        _ => (504, "Unknown protection level."),
+    };
+
+    Ok(ExecutionResult {
+        status,
+        message: message.to_string(),
+        new_state: match status {
+            200 => Some(new_state),
+            _ => None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_pbsz() {
+        let state = SessionState::default();
+        let result = prot_command_executor(&state, "P").unwrap();
+        assert_eq!(result.status, 503);
+        assert_eq!(result.message, "PBSZ required first.");
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn set_protection_level() {
+        let state = SessionState {
+            pbsz_set: true,
+            ..Default::default()
+        };
+        let result = prot_command_executor(&state, "p").unwrap();
+        assert_eq!(result.status, 200);
+        assert_eq!(result.message, "Protection level set to Private.");
+        let state = result.new_state.unwrap();
+        assert!(state.protect_data);
+
+        let result = prot_command_executor(&state, "C").unwrap();
+        assert_eq!(result.status, 200);
+        assert_eq!(result.message, "Protection level set to Clear.");
+        assert!(!result.new_state.unwrap().protect_data);
+    }
+
+    #[test]
+    fn implicit_tls_stays_private() {
+        let state = SessionState {
+            pbsz_set: true,
+            implicit_tls: true,
+            ..Default::default()
+        };
+        let result = prot_command_executor(&state, "C").unwrap();
+        assert_eq!(result.status, 534);
+        assert_eq!(result.message, "Data connections must be protected.");
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn unsupported_levels() {
+        let state = SessionState {
+            pbsz_set: true,
+            ..Default::default()
+        };
+        for arg in ["S", "E"] {
+            let result = prot_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 536);
+            assert!(result.new_state.is_none());
+        }
+        let result = prot_command_executor(&state, "X").unwrap();
+        assert_eq!(result.status, 504);
+        assert_eq!(result.message, "Unknown protection level.");
+    }
+}
@@ -39,8 +39,10 @@ mod tests {
 
     #[test]
     fn test_lf_encode() {
-        let mut state = SessionState::default();
-        state.name_prefix = PathBuf::from("/foo\n/bar");
+        let state = SessionState {
+            name_prefix: PathBuf::from("/foo\n/bar"),
+            ..Default::default()
+        };
         let result = pwd_command_executor(&state, "").unwrap();
         assert_eq!(result.status, 257);
         assert_eq!(result.message, "\"/foo\0/bar\"");
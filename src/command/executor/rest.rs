@@ -2,6 +2,13 @@ use crate::{command::errors::ExecutionError, session::sessionstate::SessionState
 
 use super::ExecutionResult;
 
+/// Sets the offset the next RETR or STOR starts at. Offsets always count
+/// bytes of the file as stored on the server, also in ASCII mode, where the
+/// data sent from that point on is translated as usual. Clients resuming an
+/// ASCII transfer must therefore count local bytes the way the server does,
+/// which is why SIZE is refused in ASCII mode. The restart markers sent in
+/// block mode are such offsets, so `REST <marker>` resumes a download from a
+/// marker. Uploads cannot be resumed from markers, see `BlockReader`.
 pub(crate) fn rest_command_executor(
     state: &SessionState,
     argument: &str,
@@ -43,8 +50,10 @@ mod tests {
 
     #[test]
     fn no_arg() {
-        let mut state = SessionState::default();
-        state.file_offset = 1337;
+        let state = SessionState {
+            file_offset: 1337,
+            ..Default::default()
+        };
         let result = rest_command_executor(&state, "").unwrap();
         assert_eq!(result.status, 350);
         assert_eq!(result.message, "Start position set to 0.");
@@ -55,8 +64,10 @@ mod tests {
 
     #[test]
     fn with_arg() {
-        let mut state = SessionState::default();
-        state.file_offset = 1337;
+        let state = SessionState {
+            file_offset: 1337,
+            ..Default::default()
+        };
         let result = rest_command_executor(&state, "420").unwrap();
         assert_eq!(result.status, 350);
         assert_eq!(result.message, "Start position set to 420.");
@@ -67,8 +78,10 @@ mod tests {
 
     #[test]
     fn no_new_state_if_unchanged() {
-        let mut state = SessionState::default();
-        state.file_offset = 1337;
+        let state = SessionState {
+            file_offset: 1337,
+            ..Default::default()
+        };
         let result = rest_command_executor(&state, "1337").unwrap();
         assert_eq!(result.status, 350);
         assert_eq!(result.message, "Start position set to 1337.");
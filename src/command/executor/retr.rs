@@ -1,38 +1,48 @@
-use std::io::{Read, Write};
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    path::Path,
+};
 
-use log::{info, warn};
+use crate::{
+    command::errors::ExecutionError,
+    session::sessionstate::{FileStructure, SessionState, TransferMode},
+    status::Status,
+};
 
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, status::Status};
-
-use super::ExecutionResult;
+use super::{transfer, ExecutionResult};
 
 pub(crate) fn retr_command_executor(
     state: &SessionState,
     argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    let file = state.name_prefix.join(argument);
-    let exists = file.exists();
+    let file = state.resolve_path(argument);
 
-    match exists {
-        false => Ok(ExecutionResult {
-            status: 550,
-            message: "File not found.".to_string(),
-            new_state: None,
-        }),
-        true => {
+    match file {
+        Ok(file) if file.is_file() => {
             let mut new_state = state.clone();
             new_state.data_transfer_func = Some(data_transfer_func);
+            let stream_mode = state.transfer_mode == TransferMode::Stream;
+            let file_structure = state.structure == FileStructure::File;
+            if state.binary_flag && stream_mode && file_structure && cfg!(target_os = "linux") {
+                new_state.data_socket_transfer_func = Some(socket_transfer_func);
+            }
             new_state.data_transfer_func_parameter = Some(file.to_str().unwrap().to_string());
+            new_state.data_transfer_translated = true;
             Ok(ExecutionResult {
                 status: 150,
                 message: "Opening data connection.".to_string(),
                 new_state: Some(new_state),
             })
         }
+        _ => Ok(ExecutionResult {
+            status: 550,
+            message: "File not found.".to_string(),
+            new_state: None,
+        }),
     }
 }
 
-// TODO handle MODE (bin, ascii)
 fn data_transfer_func(
     argument: &str,
     start_position: usize,
@@ -44,21 +54,16 @@ fn data_transfer_func(
         None => return (425, "No data connection was established.".to_string()),
     };
 
-    let file = match std::fs::read(argument) {
-        Ok(file) => file,
-        Err(error) => {
-            warn!("Error reading file: {}", error);
-            return (551, "Server error.".to_string());
-        }
-    };
+    transfer::send_file(Path::new(argument), start_position, stream)
+}
 
-    match stream.write(&file[start_position..]) {
-        Ok(count) => {
-            info!("File sent, {} bytes transmitted.", count);
-            (226, "Transfer complete.".to_string())
-        }
-        Err(_) => (426, "Error while sending data.".to_string()),
-    }
+/// Binary downloads over plain TCP, sent with `sendfile(2)` where possible.
+fn socket_transfer_func(
+    argument: &str,
+    start_position: usize,
+    socket: &TcpStream,
+) -> (Status, String) {
+    transfer::send_file_to_socket(Path::new(argument), start_position, socket)
 }
 
 #[cfg(test)]
@@ -78,8 +83,10 @@ mod tests {
 
     #[test]
     fn relative_file_exists() {
-        let mut state = SessionState::default();
-        state.name_prefix = PathBuf::from("/bin");
+        let state = SessionState {
+            name_prefix: PathBuf::from("/bin"),
+            ..Default::default()
+        };
         let result = retr_command_executor(&state, "sh").unwrap();
         assert_eq!(result.status, 150);
         assert_eq!(result.message, "Opening data connection.");
@@ -98,8 +105,65 @@ mod tests {
             new_state.data_transfer_func.unwrap() as usize,
             data_transfer_func as usize
         );
+        assert!(new_state.data_socket_transfer_func.is_none());
+        assert!(new_state.data_transfer_translated);
+        assert!(!new_state.data_transfer_upload);
         assert!(new_state.data_transfer_func_parameter.is_some());
-        assert_eq!(new_state.data_transfer_func_parameter.unwrap(), "/bin/sh");
+        assert_eq!(
+            new_state.data_transfer_func_parameter.unwrap(),
+            std::fs::canonicalize("/bin/sh").unwrap().to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn binary_mode_uses_socket() {
+        let state = SessionState {
+            binary_flag: true,
+            ..SessionState::default()
+        };
+        let new_state = retr_command_executor(&state, "/bin/sh")
+            .unwrap()
+            .new_state
+            .unwrap();
+        assert_eq!(
+            new_state.data_socket_transfer_func.is_some(),
+            cfg!(target_os = "linux")
+        );
+
+        let block_state = SessionState {
+            transfer_mode: TransferMode::Block,
+            ..state.clone()
+        };
+        let new_state = retr_command_executor(&block_state, "/bin/sh")
+            .unwrap()
+            .new_state
+            .unwrap();
+        assert!(new_state.data_socket_transfer_func.is_none());
+
+        let record_state = SessionState {
+            structure: FileStructure::Record,
+            ..state
+        };
+        let new_state = retr_command_executor(&record_state, "/bin/sh")
+            .unwrap()
+            .new_state
+            .unwrap();
+        assert!(new_state.data_socket_transfer_func.is_none());
+    }
+
+    #[test]
+    fn send_to_socket() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (socket, _) = listener.accept().unwrap();
+
+        let (status, _) = socket_transfer_func("/bin/sh", 100, &socket);
+        socket.shutdown(std::net::Shutdown::Both).unwrap();
+        assert_eq!(status, 226);
+
+        let mut received = vec![];
+        (&client).read_to_end(&mut received).unwrap();
+        assert_eq!(received, std::fs::read("/bin/sh").unwrap()[100..]);
     }
 
     #[test]
@@ -1,14 +1,95 @@
+use std::{
+    fs::remove_dir,
+    io::{self, ErrorKind},
+};
+
 use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
 
-use super::ExecutionResult;
+use super::{file_error_status, ExecutionResult};
 
 pub(crate) fn rmd_command_executor(
-    _state: &SessionState,
-    _argument: &str,
+    state: &SessionState,
+    argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    Ok(ExecutionResult {
-        status: 502,
-        message: "Command not implemented.".to_string(),
-        new_state: None,
-    })
+    if argument.is_empty() {
+        return Ok(ExecutionResult {
+            status: 501,
+            message: "Directory name required.".to_string(),
+            new_state: None,
+        });
+    }
+
+    let removed = state.resolve_entry(argument).and_then(|path| {
+        if path == state.root {
+            return Err(io::Error::new(
+                ErrorKind::PermissionDenied,
+                "Cannot remove the root directory",
+            ));
+        }
+        remove_dir(path)
+    });
+
+    let result = match removed {
+        Ok(()) => ExecutionResult {
+            status: 250,
+            message: "Okay.".to_string(),
+            new_state: None,
+        },
+        Err(error) => ExecutionResult {
+            status: file_error_status(&error),
+            message: error.to_string(),
+            new_state: None,
+        },
+    };
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn remove_empty_dir() {
+        let dir = test_dir("rmd", "empty");
+        fs::create_dir(dir.join("foo")).unwrap();
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        let result = rmd_command_executor(&state, "foo").unwrap();
+        assert_eq!(result.status, 250);
+        assert_eq!(result.message, "Okay.");
+        assert!(!dir.join("foo").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn non_empty_or_file_550() {
+        let dir = test_dir("rmd", "non-empty");
+        fs::create_dir(dir.join("foo")).unwrap();
+        fs::write(dir.join("foo").join("bar"), "").unwrap();
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        for arg in ["foo", "foo/bar", "baz"] {
+            let result = rmd_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 550);
+            assert_ne!(result.message, "");
+        }
+        assert!(dir.join("foo").join("bar").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_argument_501() {
+        let result = rmd_command_executor(&SessionState::default(), "").unwrap();
+        assert_eq!(result.status, 501);
+        assert_eq!(result.message, "Directory name required.");
+    }
 }
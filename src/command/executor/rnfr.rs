@@ -3,12 +3,68 @@ use crate::{command::errors::ExecutionError, session::sessionstate::SessionState
 use super::ExecutionResult;
 
 pub(crate) fn rnfr_command_executor(
-    _state: &SessionState,
-    _argument: &str,
+    state: &SessionState,
+    argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
+    let mut new_state = state.clone();
+    new_state.rename_from = None;
+
+    let path = state
+        .resolve_entry(argument)
+        .ok()
+        .filter(|path| path.symlink_metadata().is_ok() && path != &state.root);
+
+    let (status, message) = match path {
+        _ if argument.is_empty() => (501, "File name required."),
+        None => (550, "File not found."),
+        Some(path) => {
+            new_state.rename_from = Some(path);
+            (350, "File exists, ready for destination name.")
+        }
+    };
+
     Ok(ExecutionResult {
-        status: 502,
-        message: "Command not implemented.".to_string(),
-        new_state: None,
+        status,
+        message: message.to_string(),
+        new_state: Some(new_state),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn existing_file_350() {
+        let state = SessionState::default();
+        let result = rnfr_command_executor(&state, "/bin/sh").unwrap();
+        assert_eq!(result.status, 350);
+        assert_eq!(result.message, "File exists, ready for destination name.");
+        assert_eq!(
+            result.new_state.unwrap().rename_from,
+            Some(std::fs::canonicalize("/bin").unwrap().join("sh"))
+        );
+    }
+
+    #[test]
+    fn missing_file_clears_pending() {
+        let state = SessionState {
+            rename_from: Some(PathBuf::from("/bin/sh")),
+            ..Default::default()
+        };
+        let result = rnfr_command_executor(&state, "/lajsldf/lskdfj").unwrap();
+        assert_eq!(result.status, 550);
+        assert_eq!(result.message, "File not found.");
+        assert!(result.new_state.unwrap().rename_from.is_none());
+
+        let result = rnfr_command_executor(&state, "/").unwrap();
+        assert_eq!(result.status, 550);
+
+        let result = rnfr_command_executor(&state, "").unwrap();
+        assert_eq!(result.status, 501);
+        assert_eq!(result.message, "File name required.");
+        assert!(result.new_state.unwrap().rename_from.is_none());
+    }
+}
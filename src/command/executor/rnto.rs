@@ -1,14 +1,126 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use std::fs::rename;
 
-use super::ExecutionResult;
+use crate::{
+    command::{errors::ExecutionError, verb::Verb},
+    session::sessionstate::SessionState,
+};
+
+use super::{file_error_status, ExecutionResult};
 
 pub(crate) fn rnto_command_executor(
-    _state: &SessionState,
-    _argument: &str,
+    state: &SessionState,
+    argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    Ok(ExecutionResult {
-        status: 502,
-        message: "Command not implemented.".to_string(),
-        new_state: None,
-    })
+    let mut new_state = state.clone();
+    new_state.rename_from = None;
+
+    let mut result = ExecutionResult::default();
+
+    match (state.previous_command, state.rename_from.as_ref()) {
+        (Some(Verb::RNFR), Some(_)) if argument.is_empty() => {
+            result.status = 501;
+            result.message.push_str("File name required.");
+        }
+        (Some(Verb::RNFR), Some(source)) => {
+            match state
+                .resolve_entry(argument)
+                .and_then(|path| rename(source, path))
+            {
+                Ok(()) => {
+                    result.status = 250;
+                    result.message.push_str("Okay.");
+                }
+                Err(error) => {
+                    result.status = file_error_status(&error);
+                    result.message = error.to_string();
+                }
+            }
+        }
+        _ => {
+            result.status = 503;
+            result.message.push_str("Previous command must be RNFR.");
+        }
+    }
+
+    result.new_state = Some(new_state);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn rename_after_rnfr() {
+        let dir = test_dir("rnto", "rename");
+        fs::write(dir.join("foo"), "data").unwrap();
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            previous_command: Some(Verb::RNFR),
+            rename_from: Some(dir.join("foo")),
+            ..SessionState::default()
+        };
+
+        let result = rnto_command_executor(&state, "bar").unwrap();
+        assert_eq!(result.status, 250);
+        assert_eq!(result.message, "Okay.");
+        assert!(result.new_state.unwrap().rename_from.is_none());
+        assert!(!dir.join("foo").exists());
+        assert_eq!(fs::read(dir.join("bar")).unwrap(), b"data");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn previous_command_must_be_rnfr_503() {
+        let dir = test_dir("rnto", "stale");
+        fs::write(dir.join("foo"), "data").unwrap();
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            previous_command: Some(Verb::NOOP),
+            rename_from: Some(dir.join("foo")),
+            ..SessionState::default()
+        };
+
+        let result = rnto_command_executor(&state, "bar").unwrap();
+        assert_eq!(result.status, 503);
+        assert_eq!(result.message, "Previous command must be RNFR.");
+        assert!(result.new_state.unwrap().rename_from.is_none());
+        assert!(dir.join("foo").exists());
+        assert!(!dir.join("bar").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn no_pending_source_503() {
+        let state = SessionState {
+            previous_command: Some(Verb::RNFR),
+            ..SessionState::default()
+        };
+        let result = rnto_command_executor(&state, "bar").unwrap();
+        assert_eq!(result.status, 503);
+    }
+
+    #[test]
+    fn rename_failure_550() {
+        let dir = test_dir("rnto", "failure");
+        let mut state = SessionState {
+            name_prefix: dir.clone(),
+            previous_command: Some(Verb::RNFR),
+            rename_from: Some(dir.join("missing")),
+            ..SessionState::default()
+        };
+        let result = rnto_command_executor(&state, "bar").unwrap();
+        assert_eq!(result.status, 550);
+        assert!(result.new_state.unwrap().rename_from.is_none());
+
+        state.rename_from = Some(dir.join("missing"));
+        let result = rnto_command_executor(&state, "").unwrap();
+        assert_eq!(result.status, 501);
+        assert_eq!(result.message, "File name required.");
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
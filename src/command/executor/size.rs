@@ -0,0 +1,100 @@
+use crate::{
+    command::errors::ExecutionError,
+    session::sessionstate::{FileStructure, SessionState},
+};
+
+use super::{file_error_status, ExecutionResult};
+
+/// Replies with the size of a file (RFC 3659). The size in ASCII mode would
+/// be that of the translated file, so it is only given in binary and EBCDIC
+/// mode with file structure, where every byte is sent as one byte.
+pub(crate) fn size_command_executor(
+    state: &SessionState,
+    argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    let (status, message) = match argument {
+        "" => (501, "File name required.".to_string()),
+        _ if !state.binary_flag && state.ebcdic.is_none() => {
+            (550, "SIZE not allowed in ASCII mode.".to_string())
+        }
+        _ if state.structure == FileStructure::Record => {
+            (550, "SIZE not allowed in record structure.".to_string())
+        }
+        _ => match state
+            .resolve_path(argument)
+            .and_then(|file| file.metadata())
+        {
+            Ok(metadata) if metadata.is_file() => (213, metadata.len().to_string()),
+            Ok(_) => (550, "Not a regular file.".to_string()),
+            Err(error) => (file_error_status(&error), error.to_string()),
+        },
+    };
+
+    Ok(ExecutionResult {
+        status,
+        message,
+        new_state: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{session::ebcdic::CodePage, testing::test_dir};
+
+    use super::*;
+
+    fn binary_state() -> SessionState {
+        SessionState {
+            binary_flag: true,
+            ..SessionState::default()
+        }
+    }
+
+    #[test]
+    fn size_of_file() {
+        let dir = test_dir("size", "file");
+        let file = dir.join("data.txt");
+        fs::write(&file, "foo\nbar\n").unwrap();
+        let result = size_command_executor(&binary_state(), file.to_str().unwrap()).unwrap();
+        assert_eq!(result.status, 213);
+        assert_eq!(result.message, "8");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refused_in_ascii_mode() {
+        let result = size_command_executor(&SessionState::default(), "/bin/sh").unwrap();
+        assert_eq!(result.status, 550);
+        assert_eq!(result.message, "SIZE not allowed in ASCII mode.");
+
+        let state = SessionState {
+            ebcdic: Some(CodePage::Cp037),
+            ..SessionState::default()
+        };
+        assert_eq!(
+            size_command_executor(&state, "/bin/sh").unwrap().status,
+            213
+        );
+
+        let state = SessionState {
+            structure: FileStructure::Record,
+            ..binary_state()
+        };
+        let result = size_command_executor(&state, "/bin/sh").unwrap();
+        assert_eq!(result.status, 550);
+        assert_eq!(result.message, "SIZE not allowed in record structure.");
+    }
+
+    #[test]
+    fn invalid_targets() {
+        let result = size_command_executor(&binary_state(), "").unwrap();
+        assert_eq!(result.status, 501);
+        let result = size_command_executor(&binary_state(), "/usr").unwrap();
+        assert_eq!(result.status, 550);
+        assert_eq!(result.message, "Not a regular file.");
+        let result = size_command_executor(&binary_state(), "/lajsldf/lskdfj").unwrap();
+        assert_eq!(result.status, 550);
+    }
+}
@@ -1,14 +1,167 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use std::{
+    io::{Read, Write},
+    path::Path,
+};
 
-use super::ExecutionResult;
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, status::Status};
+
+use super::{transfer, ExecutionResult};
 
 pub(crate) fn stor_command_executor(
-    _state: &SessionState,
-    _argument: &str,
+    state: &SessionState,
+    argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
+    let file = match transfer::upload_target(state, argument) {
+        Ok(file) => file,
+        Err((status, message)) => {
+            return Ok(ExecutionResult {
+                status,
+                message,
+                new_state: None,
+            })
+        }
+    };
+
+    if state.file_offset > 0 && !offset_within_file(&file, state.file_offset) {
+        return Ok(ExecutionResult {
+            status: 554,
+            message: "Invalid REST parameter.".to_string(),
+            new_state: None,
+        });
+    }
+
+    let mut new_state = state.clone();
+    new_state.data_transfer_func = Some(data_transfer_func);
+    new_state.data_transfer_func_parameter = Some(file.to_str().unwrap().to_string());
+    new_state.data_transfer_translated = true;
+    new_state.data_transfer_upload = true;
     Ok(ExecutionResult {
-        status: 502,
-        message: "Command not implemented.".to_string(),
-        new_state: None,
+        status: 150,
+        message: "Opening data connection.".to_string(),
+        new_state: Some(new_state),
     })
 }
+
+fn offset_within_file(file: &Path, offset: usize) -> bool {
+    match file.metadata() {
+        Ok(metadata) => offset as u64 <= metadata.len(),
+        Err(_) => false,
+    }
+}
+
+fn data_transfer_func(
+    argument: &str,
+    start_position: usize,
+    read_stream: Option<&mut dyn Read>,
+    _write_stream: Option<&mut dyn Write>,
+) -> (Status, String) {
+    let stream = match read_stream {
+        Some(stream) => stream,
+        None => return (425, "No data connection was established.".to_string()),
+    };
+
+    match start_position {
+        0 => transfer::receive_file(Path::new(argument), stream),
+        _ => transfer::resume_file(Path::new(argument), start_position, stream),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn return_data_handler() {
+        let dir = test_dir("stor", "handler");
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        let result = stor_command_executor(&state, "upload.txt").unwrap();
+        assert_eq!(result.status, 150);
+        assert_eq!(result.message, "Opening data connection.");
+        assert!(result.new_state.is_some());
+        let new_state = result.new_state.unwrap();
+        assert_eq!(
+            new_state.data_transfer_func.unwrap() as usize,
+            data_transfer_func as usize
+        );
+        assert!(new_state.data_transfer_translated);
+        assert!(new_state.data_transfer_upload);
+        assert_eq!(
+            new_state.data_transfer_func_parameter.unwrap(),
+            dir.join("upload.txt").to_str().unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_argument_501() {
+        let result = stor_command_executor(&SessionState::default(), "").unwrap();
+        assert_eq!(result.status, 501);
+        assert_eq!(result.message, "File name required.");
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn invalid_target_553() {
+        let state = SessionState::default();
+        for arg in ["/usr", "/lajsldf/lskdfj/upload.txt"] {
+            let result = stor_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 553);
+            assert_eq!(result.message, "File name not allowed.");
+            assert!(result.new_state.is_none());
+        }
+    }
+
+    #[test]
+    fn offset_past_end_554() {
+        let dir = test_dir("stor", "offset");
+        fs::write(dir.join("upload.txt"), "foo").unwrap();
+        let mut state = SessionState {
+            name_prefix: dir.clone(),
+            file_offset: 3,
+            ..SessionState::default()
+        };
+        let result = stor_command_executor(&state, "upload.txt").unwrap();
+        assert_eq!(result.status, 150);
+
+        for (offset, file) in [(4, "upload.txt"), (1, "missing.txt")] {
+            state.file_offset = offset;
+            let result = stor_command_executor(&state, file).unwrap();
+            assert_eq!(result.status, 554);
+            assert_eq!(result.message, "Invalid REST parameter.");
+            assert!(result.new_state.is_none());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn handle_no_connection() {
+        let (status, msg) = data_transfer_func("", 0, None, None);
+        assert_eq!(status, 425);
+        assert_eq!(msg, "No data connection was established.");
+    }
+
+    #[test]
+    fn write_stream_to_file() {
+        let dir = test_dir("stor", "write");
+        let file = dir.join("upload.txt");
+        let (status, msg) =
+            data_transfer_func(file.to_str().unwrap(), 0, Some(&mut "foo".as_bytes()), None);
+        assert_eq!(status, 226);
+        assert_eq!(msg, "Transfer complete.");
+        assert_eq!(fs::read(&file).unwrap(), b"foo");
+
+        let _ = data_transfer_func(file.to_str().unwrap(), 0, Some(&mut "bar".as_bytes()), None);
+        assert_eq!(fs::read(&file).unwrap(), b"bar");
+
+        let _ = data_transfer_func(file.to_str().unwrap(), 3, Some(&mut "baz".as_bytes()), None);
+        assert_eq!(fs::read(&file).unwrap(), b"barbaz");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
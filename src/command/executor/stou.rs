@@ -1,14 +1,207 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use super::ExecutionResult;
+use log::warn;
+
+use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, status::Status};
+
+use super::{transfer, ExecutionResult};
+
+const DEFAULT_BASE_NAME: &str = "file";
+const MAX_UNIQUE_ATTEMPTS: usize = 10;
+
+static UNIQUE_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 pub(crate) fn stou_command_executor(
-    _state: &SessionState,
-    _argument: &str,
+    state: &SessionState,
+    argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
+    let base = match argument {
+        "" => transfer::upload_target(state, DEFAULT_BASE_NAME),
+        _ => transfer::upload_target(state, argument),
+    };
+
+    let base = match base {
+        Ok(file) => file,
+        Err((status, message)) => {
+            return Ok(ExecutionResult {
+                status,
+                message,
+                new_state: None,
+            })
+        }
+    };
+
+    let file = match reserve_unique_file(&base) {
+        Some(file) => file,
+        None => {
+            return Ok(ExecutionResult {
+                status: 450,
+                message: "Unable to create a unique file name.".to_string(),
+                new_state: None,
+            })
+        }
+    };
+
+    let name = file.file_name().unwrap().to_str().unwrap().to_string();
+
+    let mut new_state = state.clone();
+    new_state.data_transfer_func = Some(data_transfer_func);
+    new_state.data_transfer_func_parameter = Some(file.to_str().unwrap().to_string());
+    new_state.data_transfer_cleanup = Some(release_unique_file);
+    new_state.data_transfer_translated = true;
+    new_state.data_transfer_upload = true;
     Ok(ExecutionResult {
-        status: 502,
-        message: "Command not implemented.".to_string(),
-        new_state: None,
+        status: 150,
+        message: format!("FILE: {}", name),
+        new_state: Some(new_state),
     })
 }
+
+// The file is created here with O_EXCL, so two sessions can never end up
+// with the same name. The suffix is made of the time and a counter, so
+// existing files are only hit by chance and a few attempts are enough.
+fn reserve_unique_file(base: &Path) -> Option<PathBuf> {
+    let base_name = base.file_name()?.to_str()?;
+
+    for _ in 0..MAX_UNIQUE_ATTEMPTS {
+        let candidate = base.with_file_name(format!("{}.{}", base_name, unique_suffix()));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(_) => return Some(candidate),
+            Err(ref error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            Err(error) => {
+                warn!("Error creating unique file {:?}: {}", candidate, error);
+                return None;
+            }
+        }
+    }
+
+    None
+}
+
+fn unique_suffix() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis())
+        .unwrap_or_default();
+    let count = UNIQUE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{}", millis, count)
+}
+
+fn data_transfer_func(
+    argument: &str,
+    _start_position: usize,
+    read_stream: Option<&mut dyn Read>,
+    _write_stream: Option<&mut dyn Write>,
+) -> (Status, String) {
+    let stream = match read_stream {
+        Some(stream) => stream,
+        None => return (425, "No data connection was established.".to_string()),
+    };
+
+    transfer::receive_file(Path::new(argument), stream)
+}
+
+/// Releases the reserved name if nothing ended up being stored under it,
+/// whether the transfer failed or never started.
+fn release_unique_file(argument: &str) {
+    if let Err(error) = fs::remove_file(argument) {
+        warn!("Error removing unused file {}: {}", argument, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    #[test]
+    fn unique_names() {
+        let dir = test_dir("stou", "unique");
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+
+        let first = stou_command_executor(&state, "data.csv").unwrap();
+        assert_eq!(first.status, 150);
+        assert!(first.message.starts_with("FILE: data.csv."));
+        let second = stou_command_executor(&state, "data.csv").unwrap();
+        assert_eq!(second.status, 150);
+        assert!(second.message.starts_with("FILE: data.csv."));
+        assert_ne!(first.message, second.message);
+
+        let new_state = second.new_state.unwrap();
+        assert_eq!(
+            new_state.data_transfer_func.unwrap() as usize,
+            data_transfer_func as usize
+        );
+        assert_eq!(
+            new_state.data_transfer_func_parameter.unwrap(),
+            dir.join(&second.message[6..]).to_str().unwrap()
+        );
+        assert!(dir.join(&first.message[6..]).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn default_base_name() {
+        let dir = test_dir("stou", "default");
+        let state = SessionState {
+            name_prefix: dir.clone(),
+            ..SessionState::default()
+        };
+        let result = stou_command_executor(&state, "").unwrap();
+        assert_eq!(result.status, 150);
+        assert!(result
+            .message
+            .starts_with(&format!("FILE: {}.", DEFAULT_BASE_NAME)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn many_uploads_with_one_name() {
+        let dir = test_dir("stou", "many");
+        for _ in 0..2 * MAX_UNIQUE_ATTEMPTS {
+            assert!(reserve_unique_file(&dir.join("batch")).is_some());
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2 * MAX_UNIQUE_ATTEMPTS);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_target() {
+        let result = stou_command_executor(&SessionState::default(), "/usr").unwrap();
+        assert_eq!(result.status, 553);
+        assert!(result.new_state.is_none());
+    }
+
+    #[test]
+    fn handle_no_connection() {
+        let (status, msg) = data_transfer_func("", 0, None, None);
+        assert_eq!(status, 425);
+        assert_eq!(msg, "No data connection was established.");
+    }
+
+    #[test]
+    fn write_stream_to_reserved_file() {
+        let dir = test_dir("stou", "write");
+        let file = reserve_unique_file(&dir.join("upload")).unwrap();
+        let (status, msg) =
+            data_transfer_func(file.to_str().unwrap(), 0, Some(&mut "foo".as_bytes()), None);
+        assert_eq!(status, 226);
+        assert_eq!(msg, "Transfer complete.");
+        assert_eq!(fs::read(&file).unwrap(), b"foo");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
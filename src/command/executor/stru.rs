@@ -1,21 +1,35 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use crate::{
+    command::errors::ExecutionError,
+    session::sessionstate::{FileStructure, SessionState, TransferMode},
+};
 
 use super::ExecutionResult;
 
+/// Sets the file structure. Record structure is sent with the escapes of
+/// stream mode, block mode would need its own record descriptors.
 pub(crate) fn stru_command_executor(
-    _state: &SessionState,
+    state: &SessionState,
     argument: &str,
 ) -> Result<ExecutionResult, ExecutionError> {
-    let (status, message) = match argument {
-        "F" | "f" => (200, "Using file structure."),
-        "" => (504, "Parameter required."),
-        _ => (504, "Only file structure is supported."),
+    let (status, message, structure) = match argument {
+        "F" | "f" => (200, "Using file structure.", Some(FileStructure::File)),
+        "R" | "r" if state.transfer_mode == TransferMode::Block => {
+            (504, "Record structure not supported in block mode.", None)
+        }
+        "R" | "r" => (200, "Using record structure.", Some(FileStructure::Record)),
+        "" => (504, "Parameter required.", None),
+        _ => (504, "Only file and record structure are supported.", None),
     };
 
+    let new_state = structure.map(|structure| SessionState {
+        structure,
+        ..state.clone()
+    });
+
     Ok(ExecutionResult {
         status,
         message: message.to_string(),
-        new_state: None,
+        new_state,
     })
 }
 
@@ -25,12 +39,35 @@ mod tests {
 
     #[test]
     fn accept_file() {
-        let state = SessionState::default();
+        let state = SessionState {
+            structure: FileStructure::Record,
+            ..SessionState::default()
+        };
         for arg in ["F", "f"] {
             let result = stru_command_executor(&state, arg).unwrap();
             assert_eq!(result.status, 200);
-            assert_eq!(result.message, "Using file structure.")
+            assert_eq!(result.message, "Using file structure.");
+            assert_eq!(result.new_state.unwrap().structure, FileStructure::File);
+        }
+    }
+
+    #[test]
+    fn accept_record() {
+        let state = SessionState::default();
+        for arg in ["R", "r"] {
+            let result = stru_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 200);
+            assert_eq!(result.message, "Using record structure.");
+            assert_eq!(result.new_state.unwrap().structure, FileStructure::Record);
         }
+
+        let state = SessionState {
+            transfer_mode: TransferMode::Block,
+            ..state
+        };
+        let result = stru_command_executor(&state, "R").unwrap();
+        assert_eq!(result.status, 504);
+        assert!(result.new_state.is_none());
     }
 
     #[test]
@@ -41,6 +78,10 @@ mod tests {
         assert_eq!(result.message, "Parameter required.");
         let result = stru_command_executor(&state, "foobar").unwrap();
         assert_eq!(result.status, 504);
-        assert_eq!(result.message, "Only file structure is supported.");
+        assert_eq!(
+            result.message,
+            "Only file and record structure are supported."
+        );
+        assert!(result.new_state.is_none());
     }
 }
@@ -0,0 +1,527 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::{info, warn};
+
+use crate::{session::sessionstate::SessionState, status::Status};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+static UPLOAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub(super) enum CopyError {
+    Read(io::Error),
+    Write(io::Error),
+}
+
+/// Resolves the file an upload command should write to, or the reply to send
+/// if the argument cannot name an uploaded file.
+pub(super) fn upload_target(
+    state: &SessionState,
+    argument: &str,
+) -> Result<PathBuf, (Status, String)> {
+    if argument.is_empty() {
+        return Err((501, "File name required.".to_string()));
+    }
+
+    match state.resolve_path(argument) {
+        Ok(file) if !file.is_dir() && file.parent().is_some_and(Path::is_dir) => Ok(file),
+        _ => Err((553, "File name not allowed.".to_string())),
+    }
+}
+
+/// Copies everything from `reader` into `writer` using a fixed size buffer,
+/// returning the number of bytes copied.
+pub(super) fn copy_stream(reader: &mut dyn Read, writer: &mut dyn Write) -> Result<u64, CopyError> {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut total: u64 = 0;
+
+    loop {
+        let count = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(CopyError::Read(error)),
+        };
+
+        writer
+            .write_all(&buffer[..count])
+            .map_err(CopyError::Write)?;
+        total += count as u64;
+    }
+
+    writer.flush().map_err(CopyError::Write)?;
+    Ok(total)
+}
+
+/// Sends `source` to `stream` from `offset` on, without loading the whole
+/// file into memory.
+pub(super) fn send_file(source: &Path, offset: usize, stream: &mut dyn Write) -> (Status, String) {
+    let mut file = match open_for_sending(source, offset as u64) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::InvalidInput => {
+            return (554, "Invalid REST parameter.".to_string())
+        }
+        Err(error) => {
+            warn!("Error opening {:?} for sending: {}", source, error);
+            return (551, "Server error.".to_string());
+        }
+    };
+
+    send_reply(source, copy_stream(&mut file, stream))
+}
+
+/// Sends `source` to a plain TCP `socket` from `offset` on. On Linux the
+/// kernel copies the file with `sendfile(2)`, elsewhere or if the file
+/// cannot be sent that way this is the buffered copy of [`send_file`].
+pub(super) fn send_file_to_socket(
+    source: &Path,
+    offset: usize,
+    socket: &TcpStream,
+) -> (Status, String) {
+    let mut file = match open_for_sending(source, offset as u64) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::InvalidInput => {
+            return (554, "Invalid REST parameter.".to_string())
+        }
+        Err(error) => {
+            warn!("Error opening {:?} for sending: {}", source, error);
+            return (551, "Server error.".to_string());
+        }
+    };
+
+    #[cfg(target_os = "linux")]
+    match zero_copy::send(&file, offset as u64, socket) {
+        Err(error) if error.kind() == ErrorKind::Unsupported => {
+            info!("Falling back to buffered copy for {:?}: {}", source, error);
+        }
+        result => return send_reply(source, result.map_err(CopyError::Write)),
+    }
+
+    send_reply(source, copy_stream(&mut file, &mut { socket }))
+}
+
+fn send_reply(source: &Path, result: Result<u64, CopyError>) -> (Status, String) {
+    match result {
+        Ok(count) => {
+            info!("File sent, {} bytes transmitted from {:?}.", count, source);
+            (226, "Transfer complete.".to_string())
+        }
+        Err(CopyError::Read(error)) => {
+            warn!("Error while reading {:?}: {}", source, error);
+            (451, "Local error in processing.".to_string())
+        }
+        Err(CopyError::Write(error)) => {
+            warn!("Error while sending {:?}: {}", source, error);
+            (426, "Error while sending data.".to_string())
+        }
+    }
+}
+
+/// Streams `stream` into a temporary file next to `target`, and moves it in
+/// place once the whole upload has been received.
+pub(super) fn receive_file(target: &Path, stream: &mut dyn Read) -> (Status, String) {
+    let (temp_path, mut file) = match create_temp_file(target) {
+        Ok(temp) => temp,
+        Err(error) => {
+            warn!("Error creating upload file for {:?}: {}", target, error);
+            return local_error_reply(&error);
+        }
+    };
+
+    let result = copy_stream(stream, &mut file).and_then(|count| {
+        file.sync_all().map_err(CopyError::Write)?;
+        Ok(count)
+    });
+
+    let result = result.and_then(|count| {
+        fs::rename(&temp_path, target).map_err(CopyError::Write)?;
+        Ok(count)
+    });
+
+    match result {
+        Ok(count) => {
+            info!("File received, {} bytes written to {:?}.", count, target);
+            (226, "Transfer complete.".to_string())
+        }
+        Err(error) => {
+            let _ = fs::remove_file(&temp_path);
+            copy_error_reply(&error)
+        }
+    }
+}
+
+/// Continues an earlier upload by writing `stream` into `target` from
+/// `offset`, discarding anything stored past that point.
+pub(super) fn resume_file(target: &Path, offset: usize, stream: &mut dyn Read) -> (Status, String) {
+    let mut file = match open_for_resume(target, offset as u64) {
+        Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::InvalidInput => {
+            return (554, "Invalid REST parameter.".to_string())
+        }
+        Err(error) => {
+            warn!("Error opening {:?} for resumed upload: {}", target, error);
+            return local_error_reply(&error);
+        }
+    };
+
+    let result = copy_stream(stream, &mut file).and_then(|count| {
+        file.sync_all().map_err(CopyError::Write)?;
+        Ok(count)
+    });
+
+    match result {
+        Ok(count) => {
+            info!(
+                "File received, {} bytes written to {:?} from offset {}.",
+                count, target, offset
+            );
+            (226, "Transfer complete.".to_string())
+        }
+        Err(error) => copy_error_reply(&error),
+    }
+}
+
+/// Appends `stream` to `target`, creating the file if it does not exist.
+pub(super) fn append_file(target: &Path, stream: &mut dyn Read) -> (Status, String) {
+    let mut file = match OpenOptions::new().append(true).create(true).open(target) {
+        Ok(file) => file,
+        Err(error) => {
+            warn!("Error opening {:?} for appending: {}", target, error);
+            return local_error_reply(&error);
+        }
+    };
+
+    let result = copy_stream(stream, &mut file).and_then(|count| {
+        file.sync_all().map_err(CopyError::Write)?;
+        Ok(count)
+    });
+
+    match result {
+        Ok(count) => {
+            info!("File received, {} bytes appended to {:?}.", count, target);
+            (226, "Transfer complete.".to_string())
+        }
+        Err(error) => copy_error_reply(&error),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod zero_copy {
+    use std::{fs::File, io, net::TcpStream, os::fd::AsRawFd};
+
+    /// Largest count a single `sendfile` call transfers on Linux.
+    const MAX_CHUNK: u64 = 0x7fff_f000;
+
+    /// Sends `file` from `offset` to its end, returning the number of bytes
+    /// sent. Fails with `Unsupported` before sending anything if the file
+    /// cannot be sent with `sendfile`. The file position is left untouched.
+    pub(super) fn send(file: &File, offset: u64, socket: &TcpStream) -> io::Result<u64> {
+        let end = file.metadata()?.len();
+        let mut position = offset as libc::off_t;
+        let mut total = 0;
+
+        while offset + total < end {
+            let count = (end - offset - total).min(MAX_CHUNK) as usize;
+            // SAFETY: both descriptors stay open for the duration of the call
+            // and `position` is a valid pointer to an off_t.
+            let sent = unsafe {
+                libc::sendfile(socket.as_raw_fd(), file.as_raw_fd(), &mut position, count)
+            };
+
+            match sent {
+                -1 => {
+                    let error = io::Error::last_os_error();
+                    match error.raw_os_error() {
+                        Some(libc::EINTR) => continue,
+                        Some(libc::EINVAL | libc::ENOSYS) if total == 0 => {
+                            return Err(io::Error::new(io::ErrorKind::Unsupported, error))
+                        }
+                        _ => return Err(error),
+                    }
+                }
+                // The file shrank while it was being sent.
+                0 => break,
+                sent => total += sent as u64,
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+fn open_for_sending(source: &Path, offset: u64) -> io::Result<File> {
+    let mut file = File::open(source)?;
+
+    if offset > file.metadata()?.len() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "Offset past end of file",
+        ));
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(file)
+}
+
+fn open_for_resume(target: &Path, offset: u64) -> io::Result<File> {
+    let mut file = OpenOptions::new().write(true).open(target)?;
+
+    if offset > file.metadata()?.len() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "Offset past end of file",
+        ));
+    }
+
+    file.set_len(offset)?;
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(file)
+}
+
+pub(super) fn copy_error_reply(error: &CopyError) -> (Status, String) {
+    match error {
+        CopyError::Read(error) => {
+            warn!("Error while reading data stream: {}", error);
+            (426, "Connection closed, transfer aborted.".to_string())
+        }
+        CopyError::Write(error) => {
+            warn!("Error while writing file: {}", error);
+            local_error_reply(error)
+        }
+    }
+}
+
+fn local_error_reply(error: &io::Error) -> (Status, String) {
+    match error.kind() {
+        ErrorKind::StorageFull | ErrorKind::QuotaExceeded | ErrorKind::FileTooLarge => {
+            (552, "Exceeded storage allocation.".to_string())
+        }
+        _ => (451, "Local error in processing.".to_string()),
+    }
+}
+
+fn create_temp_file(target: &Path) -> io::Result<(PathBuf, File)> {
+    let file_name = match target.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return Err(io::Error::new(ErrorKind::InvalidInput, "No file name")),
+    };
+
+    let id = UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp_name = format!(".{}.{}-{}.part", file_name, process::id(), id);
+    let temp_path = target.with_file_name(temp_name);
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)?;
+
+    Ok((temp_path, file))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    struct ErrorStream {}
+    impl Read for ErrorStream {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("Fake error"))
+        }
+    }
+
+    #[test]
+    fn copy_counts_bytes() {
+        let data = vec![7u8; BUFFER_SIZE * 2 + 13];
+        let mut out = vec![];
+        let count = copy_stream(&mut data.as_slice(), &mut out).unwrap();
+        assert_eq!(count, data.len() as u64);
+        assert_eq!(out, data);
+    }
+
+    /// Accepts at most `limit` bytes, then fails like a closed connection.
+    struct ShortStream {
+        limit: usize,
+        written: usize,
+    }
+    impl Write for ShortStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written == self.limit {
+                return Err(io::Error::new(ErrorKind::WriteZero, "Connection closed"));
+            }
+            let count = buf.len().min(self.limit - self.written);
+            self.written += count;
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn send_streams_from_offset() {
+        let dir = test_dir("transfer", "send");
+        let source = dir.join("download.bin");
+        let data: Vec<u8> = (0..BUFFER_SIZE * 3 + 5).map(|i| i as u8).collect();
+        fs::write(&source, &data).unwrap();
+
+        let mut out = vec![];
+        let (status, msg) = send_file(&source, 0, &mut out);
+        assert_eq!(status, 226);
+        assert_eq!(msg, "Transfer complete.");
+        assert_eq!(out, data);
+
+        let mut out = vec![];
+        assert_eq!(send_file(&source, BUFFER_SIZE + 1, &mut out).0, 226);
+        assert_eq!(out, &data[BUFFER_SIZE + 1..]);
+
+        let mut out = vec![];
+        assert_eq!(send_file(&source, data.len(), &mut out).0, 226);
+        assert!(out.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn send_past_end_554() {
+        let dir = test_dir("transfer", "send-past-end");
+        let source = dir.join("download.bin");
+        fs::write(&source, "foo").unwrap();
+        let (status, msg) = send_file(&source, 4, &mut vec![]);
+        assert_eq!(status, 554);
+        assert_eq!(msg, "Invalid REST parameter.");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn send_short_write_426() {
+        let dir = test_dir("transfer", "send-short");
+        let source = dir.join("download.bin");
+        fs::write(&source, vec![1u8; 1000]).unwrap();
+        let mut stream = ShortStream {
+            limit: 100,
+            written: 0,
+        };
+        let (status, msg) = send_file(&source, 0, &mut stream);
+        assert_eq!(status, 426);
+        assert_eq!(msg, "Error while sending data.");
+        assert_eq!(stream.written, 100);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn receive_writes_file() {
+        let dir = test_dir("transfer", "receive");
+        let target = dir.join("upload.txt");
+        let (status, msg) = receive_file(&target, &mut "foobar".as_bytes());
+        assert_eq!(status, 226);
+        assert_eq!(msg, "Transfer complete.");
+        assert_eq!(fs::read(&target).unwrap(), b"foobar");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn receive_error_leaves_nothing_behind() {
+        let dir = test_dir("transfer", "receive-error");
+        let target = dir.join("upload.txt");
+        let (status, msg) = receive_file(&target, &mut ErrorStream {});
+        assert_eq!(status, 426);
+        assert_eq!(msg, "Connection closed, transfer aborted.");
+        assert!(!target.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn receive_missing_directory_451() {
+        let dir = test_dir("transfer", "receive-missing");
+        let target = dir.join("nope").join("upload.txt");
+        let (status, msg) = receive_file(&target, &mut "foobar".as_bytes());
+        assert_eq!(status, 451);
+        assert_eq!(msg, "Local error in processing.");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resume_writes_from_offset() {
+        let dir = test_dir("transfer", "resume");
+        let target = dir.join("upload.txt");
+        fs::write(&target, "foobarbaz").unwrap();
+        let (status, msg) = resume_file(&target, 3, &mut "BAR".as_bytes());
+        assert_eq!(status, 226);
+        assert_eq!(msg, "Transfer complete.");
+        assert_eq!(fs::read(&target).unwrap(), b"fooBAR");
+
+        let (status, _) = resume_file(&target, 6, &mut "baz".as_bytes());
+        assert_eq!(status, 226);
+        assert_eq!(fs::read(&target).unwrap(), b"fooBARbaz");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resume_past_end_554() {
+        let dir = test_dir("transfer", "resume-past-end");
+        let target = dir.join("upload.txt");
+        fs::write(&target, "foo").unwrap();
+        let (status, msg) = resume_file(&target, 4, &mut "bar".as_bytes());
+        assert_eq!(status, 554);
+        assert_eq!(msg, "Invalid REST parameter.");
+        assert_eq!(fs::read(&target).unwrap(), b"foo");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn append_creates_and_appends() {
+        let dir = test_dir("transfer", "append");
+        let target = dir.join("log.txt");
+        let (status, msg) = append_file(&target, &mut "foo".as_bytes());
+        assert_eq!(status, 226);
+        assert_eq!(msg, "Transfer complete.");
+        let (status, _) = append_file(&target, &mut "bar".as_bytes());
+        assert_eq!(status, 226);
+        assert_eq!(fs::read(&target).unwrap(), b"foobar");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn append_error_426() {
+        let dir = test_dir("transfer", "append-error");
+        let target = dir.join("log.txt");
+        let (status, _) = append_file(&target, &mut ErrorStream {});
+        assert_eq!(status, 426);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn upload_target_validation() {
+        let state = SessionState::default();
+        let (status, msg) = upload_target(&state, "").unwrap_err();
+        assert_eq!(status, 501);
+        assert_eq!(msg, "File name required.");
+        for arg in ["/usr", "/lajsldf/lskdfj/upload.txt"] {
+            let (status, msg) = upload_target(&state, arg).unwrap_err();
+            assert_eq!(status, 553);
+            assert_eq!(msg, "File name not allowed.");
+        }
+        assert_eq!(
+            upload_target(&state, "/tmp/upload.txt").unwrap(),
+            PathBuf::from("/tmp/upload.txt")
+        );
+    }
+
+    #[test]
+    fn storage_full_552() {
+        let error = CopyError::Write(io::Error::new(ErrorKind::StorageFull, "Full"));
+        assert_eq!(copy_error_reply(&error).0, 552);
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState};
+use crate::{
+    command::errors::ExecutionError,
+    session::{ebcdic::CodePage, sessionstate::SessionState},
+};
 
 use super::ExecutionResult;
 
@@ -11,12 +14,19 @@ pub(crate) fn type_command_executor(
     let (status, message) = match argument.to_ascii_uppercase().as_str() {
         "I" | "L 8" => {
             new_state.binary_flag = true;
+            new_state.ebcdic = None;
             (200, "Binary mode enabled.")
         }
         "A" | "A N" => {
             new_state.binary_flag = false;
+            new_state.ebcdic = None;
             (200, "Binary mode disabled.")
         }
+        "E" | "E N" => {
+            new_state.binary_flag = false;
+            new_state.ebcdic = Some(CodePage::configured());
+            (200, "EBCDIC mode enabled.")
+        }
         "" => (504, "Parameter required."),
         _ => (504, "Invalid parameter."),
     };
@@ -35,7 +45,7 @@ mod tests {
     #[test]
     fn binary_flag_false_by_default() {
         let state = SessionState::default();
-        assert_eq!(state.binary_flag, false);
+        assert!(!state.binary_flag);
     }
 
     #[test]
@@ -44,27 +54,48 @@ mod tests {
         let result = type_command_executor(&state, "I").unwrap();
         assert_eq!(result.status, 200);
         assert_eq!(result.message, "Binary mode enabled.");
-        assert_eq!(result.new_state.unwrap().binary_flag, true);
+        assert!(result.new_state.unwrap().binary_flag);
 
         let result = type_command_executor(&state, "L 8").unwrap();
         assert_eq!(result.status, 200);
         assert_eq!(result.message, "Binary mode enabled.");
-        assert_eq!(result.new_state.unwrap().binary_flag, true);
+        assert!(result.new_state.unwrap().binary_flag);
     }
 
     #[test]
     fn turn_binary_off() {
-        let mut state = SessionState::default();
-        state.binary_flag = true;
+        let state = SessionState {
+            binary_flag: true,
+            ..Default::default()
+        };
         let result = type_command_executor(&state, "A").unwrap();
         assert_eq!(result.status, 200);
         assert_eq!(result.message, "Binary mode disabled.");
-        assert_eq!(result.new_state.unwrap().binary_flag, false);
+        assert!(!result.new_state.unwrap().binary_flag);
 
         let result = type_command_executor(&state, "A N").unwrap();
         assert_eq!(result.status, 200);
         assert_eq!(result.message, "Binary mode disabled.");
-        assert_eq!(result.new_state.unwrap().binary_flag, false);
+        assert!(!result.new_state.unwrap().binary_flag);
+    }
+
+    #[test]
+    fn switch_to_ebcdic() {
+        let state = SessionState {
+            binary_flag: true,
+            ..SessionState::default()
+        };
+        for arg in ["E", "e n"] {
+            let result = type_command_executor(&state, arg).unwrap();
+            assert_eq!(result.status, 200);
+            assert_eq!(result.message, "EBCDIC mode enabled.");
+            let new_state = result.new_state.unwrap();
+            assert!(!new_state.binary_flag);
+            assert_eq!(new_state.ebcdic, Some(CodePage::configured()));
+
+            let result = type_command_executor(&new_state, "A").unwrap();
+            assert!(result.new_state.unwrap().ebcdic.is_none());
+        }
     }
 
     #[test]
@@ -83,17 +114,17 @@ mod tests {
         let mut state = SessionState::default();
         let result = type_command_executor(&state, "i").unwrap();
         assert_eq!(result.status, 200);
-        assert_eq!(result.new_state.unwrap().binary_flag, true);
+        assert!(result.new_state.unwrap().binary_flag);
         let result = type_command_executor(&state, "l 8").unwrap();
         assert_eq!(result.status, 200);
-        assert_eq!(result.new_state.unwrap().binary_flag, true);
+        assert!(result.new_state.unwrap().binary_flag);
 
         state.binary_flag = false;
         let result = type_command_executor(&state, "a").unwrap();
         assert_eq!(result.status, 200);
-        assert_eq!(result.new_state.unwrap().binary_flag, false);
+        assert!(!result.new_state.unwrap().binary_flag);
         let result = type_command_executor(&state, "a n").unwrap();
         assert_eq!(result.status, 200);
-        assert_eq!(result.new_state.unwrap().binary_flag, false);
+        assert!(!result.new_state.unwrap().binary_flag);
     }
 }
@@ -1,31 +1,61 @@
-use crate::{command::errors::ExecutionError, session::sessionstate::SessionState, status::Status};
+use crate::{
+    auth::{self, Authenticator, Identity},
+    command::errors::ExecutionError,
+    config,
+    session::sessionstate::SessionState,
+    status::Status,
+};
 
 use super::ExecutionResult;
 
 pub(crate) fn user_command_executor(
     state: &SessionState,
     argument: &str,
+) -> Result<ExecutionResult, ExecutionError> {
+    if config::require_tls() && !state.tls_active {
+        return Ok(ExecutionResult {
+            status: 534,
+            message: "TLS is required, use AUTH TLS first.".to_string(),
+            new_state: None,
+        });
+    }
+
+    user_command_executor_with_authenticator(state, argument, auth::authenticator())
+}
+
+fn user_command_executor_with_authenticator(
+    state: &SessionState,
+    argument: &str,
+    authenticator: &dyn Authenticator,
 ) -> Result<ExecutionResult, ExecutionError> {
     let mut result = ExecutionResult::default();
+    let mut identity = None;
 
     match argument {
         "" => {
             result.status = 501;
             result.message.push_str("User name parameter empty.")
         }
-        "anonymous" => {
-            result.status = 230;
-            result
-                .message
-                .push_str("Anonymous login ok, public access granted.");
-        }
+        "anonymous" => match auth::login(authenticator, argument, "") {
+            Some(anonymous) => {
+                identity = Some(anonymous);
+                result.status = 230;
+                result
+                    .message
+                    .push_str("Anonymous login ok, public access granted.");
+            }
+            None => {
+                result.status = 331;
+                result.message.push_str("User name okay, need password.");
+            }
+        },
         _ => {
             result.status = 331;
             result.message.push_str("User name okay, need password.");
         }
     }
 
-    result.new_state = user_update_state(&state, result.status, argument);
+    result.new_state = user_update_state(&state, result.status, argument, identity);
     Ok(result)
 }
 
@@ -33,11 +63,16 @@ fn user_update_state(
     current_state: &SessionState,
     status: Status,
     username: &str,
+    identity: Option<Identity>,
 ) -> Option<SessionState> {
     if status == 230 || status == 331 {
         let mut new_state = current_state.clone();
         new_state.user = Some(username.to_string());
-        new_state.is_authenticated = status == 230;
+        new_state.is_authenticated = false;
+        new_state.identity = None;
+        if let Some(identity) = identity {
+            new_state.log_in(identity);
+        }
         Some(new_state)
     } else {
         None
@@ -80,6 +115,28 @@ mod tests {
         assert!(new_state.is_authenticated);
     }
 
+    struct NoAnonymous {}
+    impl Authenticator for NoAnonymous {
+        fn authenticate(&self, _username: &str, _password: &str) -> Option<Identity> {
+            None
+        }
+    }
+
+    #[test]
+    fn user_anonymous_disabled_returns_331() {
+        let state = SessionState {
+            is_authenticated: true,
+            ..Default::default()
+        };
+        let result =
+            user_command_executor_with_authenticator(&state, "anonymous", &NoAnonymous {}).unwrap();
+        assert_eq!(result.status, 331);
+        assert_eq!(result.message, "User name okay, need password.");
+        let new_state = result.new_state.unwrap();
+        assert!(!new_state.is_authenticated);
+        assert!(new_state.identity.is_none());
+    }
+
     #[test]
     fn user_no_argument_returns_501() {
         let state = SessionState::default();
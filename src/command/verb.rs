@@ -5,18 +5,25 @@ use log::warn;
 use super::executor::acct::acct_command_executor;
 use super::executor::allo::allo_command_executor;
 use super::executor::appe::appe_command_executor;
+use super::executor::auth::auth_command_executor;
 use super::executor::cdup::cdup_command_executor;
 use super::executor::cwd::cwd_command_executor;
 use super::executor::dele::dele_command_executor;
+use super::executor::eprt::eprt_command_executor;
+use super::executor::epsv::epsv_command_executor;
+use super::executor::feat::feat_command_executor;
 use super::executor::help::help_command_executor;
 use super::executor::list::list_command_executor;
 use super::executor::mkd::mkd_command_executor;
 use super::executor::mode::mode_command_executor;
 use super::executor::nlst::nlst_command_executor;
 use super::executor::noop::noop_command_executor;
+use super::executor::opts::opts_command_executor;
 use super::executor::pass::pass_command_executor;
 use super::executor::pasv::pasv_command_executor;
+use super::executor::pbsz::pbsz_command_executor;
 use super::executor::port::port_command_executor;
+use super::executor::prot::prot_command_executor;
 use super::executor::pwd::pwd_command_executor;
 use super::executor::quit::quit_command_executor;
 use super::executor::r#type::type_command_executor;
@@ -25,6 +32,7 @@ use super::executor::retr::retr_command_executor;
 use super::executor::rmd::rmd_command_executor;
 use super::executor::rnfr::rnfr_command_executor;
 use super::executor::rnto::rnto_command_executor;
+use super::executor::size::size_command_executor;
 use super::executor::stat::stat_command_executor;
 use super::executor::stor::stor_command_executor;
 use super::executor::stou::stou_command_executor;
@@ -70,6 +78,14 @@ pub enum Verb {
     DELE,
     RNTO,
     RNFR,
+    FEAT,
+    AUTH,
+    PBSZ,
+    PROT,
+    EPSV,
+    EPRT,
+    SIZE,
+    OPTS,
 }
 
 impl FromStr for Verb {
@@ -112,6 +128,14 @@ impl FromStr for Verb {
             "DELE" => Ok(Verb::DELE),
             "RNFR" => Ok(Verb::RNFR),
             "RNTO" => Ok(Verb::RNTO),
+            "FEAT" => Ok(Verb::FEAT),
+            "AUTH" => Ok(Verb::AUTH),
+            "PBSZ" => Ok(Verb::PBSZ),
+            "PROT" => Ok(Verb::PROT),
+            "EPSV" => Ok(Verb::EPSV),
+            "EPRT" => Ok(Verb::EPRT),
+            "SIZE" => Ok(Verb::SIZE),
+            "OPTS" => Ok(Verb::OPTS),
             _ => {
                 warn!("Unknown verb: {}", s);
                 Err(format!("Unknown verb: {}", s))
@@ -121,6 +145,46 @@ impl FromStr for Verb {
 }
 
 impl Verb {
+    /// Whether the verb may only be used once the session is logged in.
+    pub(super) fn requires_login(&self) -> bool {
+        !matches!(
+            self,
+            Verb::USER
+                | Verb::PASS
+                | Verb::ACCT
+                | Verb::QUIT
+                | Verb::HELP
+                | Verb::FEAT
+                | Verb::SYST
+                | Verb::NOOP
+                | Verb::AUTH
+                | Verb::PBSZ
+                | Verb::PROT
+        )
+    }
+
+    /// Whether the verb reads files and needs the read permission.
+    pub(super) fn requires_read(&self) -> bool {
+        matches!(self, Verb::RETR | Verb::LIST | Verb::NLST | Verb::SIZE)
+    }
+
+    /// Whether the verb modifies the file system and needs the write permission.
+    pub(super) fn requires_write(&self) -> bool {
+        matches!(
+            self,
+            Verb::STOR
+                | Verb::APPE
+                | Verb::STOU
+                | Verb::MKD
+                | Verb::XMKD
+                | Verb::RMD
+                | Verb::XRMD
+                | Verb::DELE
+                | Verb::RNFR
+                | Verb::RNTO
+        )
+    }
+
     pub(super) fn executor(&self) -> Executor {
         match self {
             Verb::USER => user_command_executor,
@@ -152,6 +216,14 @@ impl Verb {
             Verb::DELE => dele_command_executor,
             Verb::RNFR => rnfr_command_executor,
             Verb::RNTO => rnto_command_executor,
+            Verb::FEAT => feat_command_executor,
+            Verb::AUTH => auth_command_executor,
+            Verb::PBSZ => pbsz_command_executor,
+            Verb::PROT => prot_command_executor,
+            Verb::EPSV => epsv_command_executor,
+            Verb::EPRT => eprt_command_executor,
+            Verb::SIZE => size_command_executor,
+            Verb::OPTS => opts_command_executor,
         }
     }
 }
@@ -191,6 +263,36 @@ mod tests {
         assert!(Verb::from_str(empty).is_err());
     }
 
+    #[test]
+    fn test_requires_login() {
+        for verb in [
+            "USER", "PASS", "ACCT", "QUIT", "HELP", "FEAT", "SYST", "NOOP", "AUTH", "PBSZ", "PROT",
+        ] {
+            assert!(!Verb::from_str(verb).unwrap().requires_login());
+        }
+        for verb in [
+            "RETR", "LIST", "CWD", "PASV", "PORT", "EPSV", "EPRT", "STOR", "DELE",
+        ] {
+            assert!(Verb::from_str(verb).unwrap().requires_login());
+        }
+    }
+
+    #[test]
+    fn test_required_permissions() {
+        for verb in ["RETR", "LIST", "NLST", "SIZE"] {
+            assert!(Verb::from_str(verb).unwrap().requires_read());
+            assert!(!Verb::from_str(verb).unwrap().requires_write());
+        }
+        for verb in ["STOR", "APPE", "STOU", "MKD", "RMD", "DELE", "RNFR", "RNTO"] {
+            assert!(!Verb::from_str(verb).unwrap().requires_read());
+            assert!(Verb::from_str(verb).unwrap().requires_write());
+        }
+        for verb in ["CWD", "PWD", "USER", "PASV"] {
+            assert!(!Verb::from_str(verb).unwrap().requires_read());
+            assert!(!Verb::from_str(verb).unwrap().requires_write());
+        }
+    }
+
     #[test]
     fn test_executor_mapping() {
         assert_eq!(
@@ -317,5 +419,37 @@ mod tests {
             Verb::RNTO.executor() as usize,
             rnto_command_executor as usize
         );
+        assert_eq!(
+            Verb::FEAT.executor() as usize,
+            feat_command_executor as usize
+        );
+        assert_eq!(
+            Verb::AUTH.executor() as usize,
+            auth_command_executor as usize
+        );
+        assert_eq!(
+            Verb::PBSZ.executor() as usize,
+            pbsz_command_executor as usize
+        );
+        assert_eq!(
+            Verb::PROT.executor() as usize,
+            prot_command_executor as usize
+        );
+        assert_eq!(
+            Verb::EPSV.executor() as usize,
+            epsv_command_executor as usize
+        );
+        assert_eq!(
+            Verb::EPRT.executor() as usize,
+            eprt_command_executor as usize
+        );
+        assert_eq!(
+            Verb::SIZE.executor() as usize,
+            size_command_executor as usize
+        );
+        assert_eq!(
+            Verb::OPTS.executor() as usize,
+            opts_command_executor as usize
+        );
     }
 }
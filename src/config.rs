@@ -1,3 +1,12 @@
+use std::{
+    env,
+    fs::canonicalize,
+    net::{IpAddr, Ipv6Addr},
+    ops::RangeInclusive,
+    path::PathBuf,
+    time::Duration,
+};
+
 // Default values come from:
 // http://sup.xenya.si/sup/info/Juniper/ScreenOS_5.4.0/DocCD_files/Help/5.4.0/ftp_service.htm
 
@@ -10,6 +19,155 @@ pub const MAX_LINE_LENGTH: usize = 1024;
 // pub const MAX_LOGIN_FAILURES_PER_MINUTE: usize = 10;
 
 pub const LISTEN_PORT: u16 = 2121;
-pub const LISTEN_ADDR: &str = "0.0.0.0";
+pub const LISTEN_ADDR: IpAddr = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
 
 pub const NAME_PREFIX: &str = "/";
+
+pub const DEFAULT_DATA_ACCEPT_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_DATA_CONNECT_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_RESTART_MARKER_INTERVAL: u64 = 1024 * 1024;
+pub const DEFAULT_DEFLATE_LEVEL: u32 = 6;
+
+pub const DEFAULT_ANONYMOUS_FLAGS: &str = "r";
+
+/// The real directory that sessions see as `/`, set with `FETP_ROOT`. There
+/// is no default, so that the whole host is never served by accident.
+pub fn root_dir() -> Option<PathBuf> {
+    let root = PathBuf::from(env::var_os("FETP_ROOT")?);
+    Some(canonicalize(&root).unwrap_or(root))
+}
+
+/// The root of sessions and anonymous logins. The server does not start
+/// without `FETP_ROOT`, only tests run without it and see the real root.
+pub fn session_root_dir() -> PathBuf {
+    root_dir().unwrap_or_else(|| PathBuf::from("/"))
+}
+
+/// Whether the `anonymous` user may log in, set with `FETP_ANONYMOUS`.
+pub fn anonymous_enabled() -> bool {
+    env_flag("FETP_ANONYMOUS", true)
+}
+
+/// Permission flags for the `anonymous` user, set with `FETP_ANONYMOUS_FLAGS`.
+pub fn anonymous_flags() -> String {
+    env::var("FETP_ANONYMOUS_FLAGS").unwrap_or_else(|_| DEFAULT_ANONYMOUS_FLAGS.to_string())
+}
+
+/// Static users file, set with `FETP_USERS_FILE`.
+pub fn users_file() -> Option<PathBuf> {
+    env::var_os("FETP_USERS_FILE").map(PathBuf::from)
+}
+
+/// Password file with hashed passwords, set with `FETP_PASSWD_FILE`.
+pub fn passwd_file() -> Option<PathBuf> {
+    env::var_os("FETP_PASSWD_FILE").map(PathBuf::from)
+}
+
+/// PEM certificate chain used for TLS, set with `FETP_TLS_CERT`.
+pub fn tls_cert_file() -> Option<PathBuf> {
+    env::var_os("FETP_TLS_CERT").map(PathBuf::from)
+}
+
+/// PEM private key used for TLS, set with `FETP_TLS_KEY`.
+pub fn tls_key_file() -> Option<PathBuf> {
+    env::var_os("FETP_TLS_KEY").map(PathBuf::from)
+}
+
+/// Port of the implicit FTPS listener (usually 990), set with
+/// `FETP_IMPLICIT_TLS_PORT`. The listener is disabled when unset.
+pub fn implicit_tls_port() -> Option<u16> {
+    env::var("FETP_IMPLICIT_TLS_PORT").ok()?.parse().ok()
+}
+
+/// Whether clients must use AUTH TLS before logging in, set with
+/// `FETP_REQUIRE_TLS`.
+pub fn require_tls() -> bool {
+    env_flag("FETP_REQUIRE_TLS", false)
+}
+
+/// Ports used for passive data connections, set with `FETP_PASV_PORTS` as
+/// `first-last`. Any free port is used when unset.
+pub fn passive_ports() -> Option<RangeInclusive<u16>> {
+    let value = env::var("FETP_PASV_PORTS").ok()?;
+    let (first, last) = value.split_once('-')?;
+    let range = first.trim().parse().ok()?..=last.trim().parse().ok()?;
+    match range.is_empty() {
+        true => None,
+        false => Some(range),
+    }
+}
+
+/// Address or host name advertised in PASV replies instead of the local
+/// address, for servers behind NAT. Set with `FETP_PASV_ADDRESS`.
+pub fn passive_address() -> Option<String> {
+    env::var("FETP_PASV_ADDRESS").ok()
+}
+
+/// EBCDIC code page used for TYPE E, such as `037` or `1047`. Set with
+/// `FETP_EBCDIC_CODE_PAGE`.
+pub fn ebcdic_code_page() -> Option<String> {
+    env::var("FETP_EBCDIC_CODE_PAGE").ok()
+}
+
+/// How many bytes of a file are sent between restart markers in block mode,
+/// set with `FETP_RESTART_MARKER_INTERVAL`.
+pub fn restart_marker_interval() -> u64 {
+    env_number(
+        "FETP_RESTART_MARKER_INTERVAL",
+        DEFAULT_RESTART_MARKER_INTERVAL,
+    )
+}
+
+/// How long to wait for the client to open a passive data connection, set
+/// with `FETP_DATA_ACCEPT_TIMEOUT` in seconds.
+pub fn data_accept_timeout() -> Duration {
+    env_seconds("FETP_DATA_ACCEPT_TIMEOUT", DEFAULT_DATA_ACCEPT_TIMEOUT_SECS)
+}
+
+/// How long to wait when connecting to the client in active mode, set with
+/// `FETP_DATA_CONNECT_TIMEOUT` in seconds.
+pub fn data_connect_timeout() -> Duration {
+    env_seconds(
+        "FETP_DATA_CONNECT_TIMEOUT",
+        DEFAULT_DATA_CONNECT_TIMEOUT_SECS,
+    )
+}
+
+/// Whether any session may exchange data with another host than the one on
+/// the control connection, as needed for FXP, and target privileged ports in
+/// active mode. Set with `FETP_ALLOW_FXP`.
+pub fn allow_fxp() -> bool {
+    env_flag("FETP_ALLOW_FXP", false)
+}
+
+/// Addresses and user names that may do FXP transfers, set with
+/// `FETP_FXP_ALLOW` as a comma separated list. They may also target
+/// privileged ports in active mode.
+pub fn fxp_allow_list() -> Vec<String> {
+    env::var("FETP_FXP_ALLOW")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn env_seconds(name: &str, default: u64) -> Duration {
+    Duration::from_secs(env_number(name, default))
+}
+
+fn env_number(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&number| number > 0)
+        .unwrap_or(default)
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match env::var(name) {
+        Ok(value) => !matches!(value.to_lowercase().as_str(), "0" | "false" | "no" | "off"),
+        Err(_) => default,
+    }
+}
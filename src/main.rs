@@ -3,20 +3,126 @@ mod command;
 mod config;
 mod session;
 mod status;
+#[cfg(test)]
+mod testing;
+mod tls;
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use socket2::{Domain, Protocol, Socket, Type};
 
 use std::{
-    net::{TcpListener, TcpStream},
-    thread,
+    env,
+    io::{self, IsTerminal},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    process, thread,
 };
 
 fn main() {
     init_logger();
 
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("passwd") {
+        process::exit(passwd(&args[2..]));
+    }
+
     info!("Starting FeTP server...");
 
-    listen(session::handle_new_connection);
+    let root = match config::root_dir() {
+        Some(root) => root,
+        None => {
+            error!("FETP_ROOT must be set to the directory to serve");
+            std::process::exit(1);
+        }
+    };
+    if !root.is_dir() {
+        error!("Root directory {} does not exist", root.display());
+        std::process::exit(1);
+    }
+    if root == Path::new("/") {
+        warn!("Serving the whole file system, point FETP_ROOT at a dedicated directory");
+    }
+    info!("Serving files from {}", root.display());
+
+    if let Some(port) = config::implicit_tls_port() {
+        if tls::server_config().is_none() {
+            error!("Implicit TLS requires FETP_TLS_CERT and FETP_TLS_KEY to be set");
+            std::process::exit(1);
+        }
+
+        let listener = create_tcp_listener(port);
+        thread::spawn(move || listen(listener, session::handle_new_tls_connection));
+    }
+
+    listen(
+        create_tcp_listener(config::LISTEN_PORT),
+        session::handle_new_connection,
+    );
+}
+
+/// `fetp passwd <file> <user> [home] [flags]`, reads the new password from
+/// stdin and stores its hash in the password file.
+fn passwd(args: &[String]) -> i32 {
+    if args.len() < 2 || args.len() > 4 {
+        eprintln!("Usage: fetp passwd <file> <user> [home] [flags]");
+        return 2;
+    }
+
+    let password = match read_new_password(&args[1]) {
+        Ok(password) => password,
+        Err(e) => {
+            eprintln!("Failed to read password: {}", e);
+            return 1;
+        }
+    };
+
+    let hash = match auth::passwd::hash_password(&password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!("Failed to hash password: {}", e);
+            return 1;
+        }
+    };
+
+    let home = args.get(2).map(String::as_str);
+    let flags = args.get(3).map(String::as_str);
+    match auth::passwd::set_entry(Path::new(&args[0]), &args[1], &hash, home, flags) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Failed to update {}: {}", args[0], e);
+            1
+        }
+    }
+}
+
+/// Reads the new password from stdin. On a terminal it is read twice and
+/// without echo, otherwise a single line is read, for use in scripts.
+fn read_new_password(user: &str) -> io::Result<String> {
+    let password = match io::stdin().is_terminal() {
+        true => {
+            let password = rpassword::prompt_password(format!("Enter password for {}: ", user))?;
+            if rpassword::prompt_password("Repeat password: ")? != password {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Passwords do not match",
+                ));
+            }
+            password
+        }
+        false => {
+            let mut password = String::new();
+            io::stdin().read_line(&mut password)?;
+            password.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    match password.is_empty() {
+        true => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Password cannot be empty",
+        )),
+        false => Ok(password),
+    }
 }
 
 fn init_logger() {
@@ -25,9 +131,7 @@ fn init_logger() {
     debug!("Logger initialized");
 }
 
-fn listen(handler: fn(TcpStream)) {
-    let listener = create_tcp_listener();
-
+fn listen(listener: TcpListener, handler: fn(TcpStream)) {
     info!("Ready to accept connections");
 
     for stream in listener.incoming() {
@@ -46,23 +150,45 @@ fn listen(handler: fn(TcpStream)) {
     }
 }
 
-fn create_tcp_listener() -> TcpListener {
-    let listener = match TcpListener::bind(listen_address_formatted()) {
+fn create_tcp_listener(port: u16) -> TcpListener {
+    let address = SocketAddr::new(config::LISTEN_ADDR, port);
+
+    let listener = match bind_dual_stack(address) {
         Ok(listener) => listener,
+        Err(e) if address.is_ipv6() => {
+            warn!("Failed to bind to address {}: {}, trying IPv4", address, e);
+            let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port);
+            match bind_dual_stack(address) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Failed to bind to address {}: {}", address, e);
+                    std::process::exit(1);
+                }
+            }
+        }
         Err(e) => {
-            error!(
-                "Failed to bind to address {}: {}",
-                listen_address_formatted(),
-                e
-            );
+            error!("Failed to bind to address {}: {}", address, e);
             std::process::exit(1);
         }
     };
 
     info!("Listening on {}", listener.local_addr().unwrap());
-    return listener;
+    listener
 }
 
-fn listen_address_formatted() -> String {
-    format!("{}:{}", config::LISTEN_ADDR, config::LISTEN_PORT)
+/// Binds a listener that accepts both IPv4 and IPv6 clients when `address`
+/// is an IPv6 address.
+fn bind_dual_stack(address: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if address.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(128)?;
+    Ok(socket.into())
 }
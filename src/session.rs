@@ -1,21 +1,38 @@
+mod block;
+mod data;
+mod deflate;
+pub(crate) mod ebcdic;
 mod io;
+mod record;
+mod representation;
 pub mod sessionstate;
+mod vfs;
 
 use std::{
-    io::{Read, Write},
+    cell::Cell,
+    io::{ErrorKind, Read, Write},
     net::TcpStream,
+    sync::Arc,
 };
 
+use flate2::read::ZlibDecoder;
 use log::{error, info, warn};
+use rustls::ServerConfig;
 
 use crate::{
     command::{self, errors::CommandError, verb::Verb, Command},
-    session::io::write,
+    config,
+    session::io::{write, write_multiline},
     status::Status,
+    tls::{self, TlsStream},
 };
 
+use self::block::{BlockReader, BlockWriter, Counter};
+use self::deflate::DeflateWriter;
 use self::io::read_line;
-use self::sessionstate::SessionState;
+use self::record::{RecordReader, RecordWriter};
+use self::representation::{AsciiReader, AsciiWriter, EbcdicReader, EbcdicWriter};
+use self::sessionstate::{FileStructure, SessionState, TransferMode};
 
 struct Session {
     socket: TcpStream,
@@ -35,6 +52,23 @@ pub(crate) fn handle_new_connection(socket: TcpStream) {
     run_session(&mut session, handle_pass);
 }
 
+/// Handles a connection on the implicit FTPS port, where TLS is negotiated
+/// before the greeting and all data connections are protected.
+pub(crate) fn handle_new_tls_connection(socket: TcpStream) {
+    let mut session = Session::new(socket);
+
+    if let Err(error) = secure_control_connection(&mut session) {
+        warn!("TLS handshake on implicit connection failed: {}", error);
+        end_session(&mut session.socket);
+        return;
+    }
+
+    session.state.implicit_tls = true;
+    session.state.pbsz_set = true;
+    session.state.protect_data = true;
+    run_session(&mut session, handle_pass);
+}
+
 impl Session {
     fn new(stream: TcpStream) -> Session {
         let read_socket = Box::new(stream.try_clone().expect("Failed to clone stream"));
@@ -81,7 +115,14 @@ fn handle_pass(session: &mut Session) -> ShouldExit {
     let ((status, message), result) = run_command(&command, &session.state);
     session.state = result;
 
-    let mut should_exit = write_result_to_peer(&mut session.write_socket, status, &message);
+    let mut should_exit = match command.verb {
+        Verb::FEAT => write_feat_to_peer(&mut session.write_socket, status, &message),
+        _ => write_result_to_peer(&mut session.write_socket, status, &message),
+    };
+
+    if session.state.tls_pending {
+        should_exit = upgrade_to_tls(session);
+    }
 
     if session.state.previous_command == Some(Verb::QUIT) {
         return ShouldExit::Yes;
@@ -95,6 +136,33 @@ fn handle_pass(session: &mut Session) -> ShouldExit {
     should_exit
 }
 
+/// Replaces the control streams with TLS ones after a successful AUTH.
+fn upgrade_to_tls(session: &mut Session) -> ShouldExit {
+    session.state.tls_pending = false;
+
+    match secure_control_connection(session) {
+        Ok(()) => ShouldExit::No,
+        Err(error) => {
+            warn!("TLS handshake on control connection failed: {}", error);
+            ShouldExit::Yes
+        }
+    }
+}
+
+fn secure_control_connection(session: &mut Session) -> std::io::Result<()> {
+    let config = match tls::server_config() {
+        Some(config) => config,
+        None => return Err(std::io::Error::other("TLS is not configured")),
+    };
+
+    let stream = TlsStream::accept(config, session.socket.try_clone()?)?;
+    info!("Control connection secured with TLS");
+    session.read_socket = Box::new(stream.clone());
+    session.write_socket = Box::new(stream);
+    session.state.tls_active = true;
+    Ok(())
+}
+
 fn handle_session_not_greeted(session: &mut Session) -> ShouldExit {
     session.state.has_greeted = true;
     greet_new_connection(&mut session.write_socket)
@@ -105,7 +173,24 @@ fn greet_new_connection(stream: &mut impl Write) -> ShouldExit {
 }
 
 fn write_result_to_peer(stream: &mut impl Write, status: Status, message: &str) -> ShouldExit {
-    match write(stream, status, message) {
+    let result = match message.contains('\n') {
+        false => write(stream, status, message),
+        true => {
+            let lines: Vec<&str> = message.split('\n').collect();
+            write_multiline(stream, status, &lines).map(|written| written.iter().sum())
+        }
+    };
+
+    check_written(result)
+}
+
+/// Writes the reply to FEAT, which has a layout of its own.
+fn write_feat_to_peer(stream: &mut impl Write, status: Status, message: &str) -> ShouldExit {
+    check_written(command::write_feat_reply(stream, status, message))
+}
+
+fn check_written(result: std::io::Result<usize>) -> ShouldExit {
+    match result {
         Ok(written) => {
             info!("Wrote {} bytes.", written);
             ShouldExit::No
@@ -124,7 +209,7 @@ fn await_command(stream: &mut impl Read) -> Result<Command, (Status, String)> {
         Ok(command) => Ok(command),
         Err(error) => {
             warn!("Error reading command: {}", error.to_string());
-            return Err((500, error.to_string()));
+            Err((500, error.to_string()))
         }
     }
 }
@@ -150,59 +235,263 @@ fn run_command(
     ((result.status, result.message), new_state)
 }
 
-// TODO Unit tests
 fn process_data_request(state: &mut SessionState) -> (Status, String) {
-    if (state.data_listener.is_none() && state.port_ip.is_none())
-        || state.data_transfer_func.is_none()
-    {
-        return (425, "No data connection was established.".to_string());
+    let result = match state.data_transfer_func {
+        None => (425, "No data connection was established.".to_string()),
+        Some(_) => match open_data_connection(state) {
+            Err(reply) => reply,
+            Ok(socket) => {
+                let result = match state.protect_data {
+                    true => run_protected_transfer(state, &socket, tls::server_config()),
+                    false if state.data_socket_transfer_func.is_some() => {
+                        run_socket_transfer(state, &socket)
+                    }
+                    false => {
+                        let mut read_stream = socket.try_clone().unwrap();
+                        let mut write_stream = socket.try_clone().unwrap();
+                        run_transfer(state, &mut read_stream, &mut write_stream)
+                    }
+                };
+                log_transfer(state, &socket, &result);
+                let _ = socket.shutdown(std::net::Shutdown::Both);
+                result
+            }
+        },
+    };
+
+    if let (true, Some(cleanup)) = (result.0 != 226, state.data_transfer_cleanup) {
+        cleanup(state.data_transfer_func_parameter.as_deref().unwrap_or(""));
     }
 
-    if let Some(listener) = state.data_listener.as_mut() {
-        listener.set_nonblocking(true).unwrap();
+    state.data_listener = None;
+    state.data_transfer_func = None;
+    state.data_socket_transfer_func = None;
+    state.data_transfer_func_parameter = None;
+    state.data_transfer_cleanup = None;
+    state.data_transfer_translated = false;
+    state.data_transfer_upload = false;
+    state.file_offset = 0;
+
+    result
+}
+
+/// Records a finished transfer with both data endpoints. Transfers where the
+/// data went to another host than the client's are marked as FXP.
+fn log_transfer(state: &SessionState, socket: &TcpStream, result: &(Status, String)) {
+    let (local, remote) = match (socket.local_addr(), socket.peer_addr()) {
+        (Ok(local), Ok(remote)) => (local, remote),
+        _ => return,
+    };
+    let user = state.user.as_deref().unwrap_or("-");
+    let parameter = state.data_transfer_func_parameter.as_deref().unwrap_or("");
+
+    match remote.ip().to_canonical() == state.peer_ip {
+        true => info!(
+            "Transfer {} \"{}\" for {}: {} <-> {}",
+            result.0, parameter, user, local, remote
+        ),
+        false => info!(
+            "FXP transfer {} \"{}\" for {} at {}: {} <-> {}",
+            result.0, parameter, user, state.peer_ip, local, remote
+        ),
+    }
+}
 
-        // ! This is probably a race condition, handle this with a timeout?
-        state.data_socket = match listener.accept() {
-            Ok((stream, _)) => Some(stream),
-            Err(error) => {
-                warn!("Error accepting data connection: {}", error);
-                return (425, "Data connection failed.".to_string());
+/// Waits for the client on the passive listener, or connects to the address
+/// given with PORT.
+fn open_data_connection(state: &SessionState) -> Result<TcpStream, (Status, String)> {
+    if let Some(listener) = state.data_listener.as_ref() {
+        let timeout = config::data_accept_timeout();
+        let allow_foreign = |host| state.fxp_allowed(host);
+        return data::accept(listener, timeout, state.peer_ip, &allow_foreign).map_err(|error| {
+            warn!("Error accepting data connection: {}", error);
+            match error.kind() {
+                ErrorKind::TimedOut => (425, "Data connection timed out.".to_string()),
+                _ => (425, "Data connection failed.".to_string()),
             }
-        };
-    } else if let Some(address) = state.port_ip {
-        let socket = match TcpStream::connect(address) {
-            Ok(socket) => Some(socket),
-            Err(_) => None,
-        };
+        });
+    }
+
+    if let Some(address) = state.port_ip {
+        return TcpStream::connect_timeout(&address, config::data_connect_timeout()).map_err(
+            |error| {
+                warn!("Error connecting to {}: {}", address, error);
+                (425, "Can't open data connection.".to_string())
+            },
+        );
+    }
+
+    Err((425, "No data connection was established.".to_string()))
+}
+
+/// Runs the transfer in the transfer mode chosen with MODE.
+fn run_transfer(
+    state: &SessionState,
+    read_stream: &mut dyn Read,
+    write_stream: &mut dyn Write,
+) -> (Status, String) {
+    match state.transfer_mode {
+        TransferMode::Stream => run_translated_transfer(state, read_stream, write_stream, None),
+        TransferMode::Block => run_block_transfer(state, read_stream, write_stream),
+        TransferMode::Deflate => run_deflate_transfer(state, read_stream, write_stream),
+    }
+}
+
+/// Compresses the data with deflate, after any translation.
+fn run_deflate_transfer(
+    state: &SessionState,
+    read_stream: &mut dyn Read,
+    write_stream: &mut dyn Write,
+) -> (Status, String) {
+    let mut reader = ZlibDecoder::new(read_stream);
+    let mut writer = DeflateWriter::new(write_stream, state.deflate_level);
+
+    let result = run_translated_transfer(state, &mut reader, &mut writer, None);
+    finish_download(state, result, || writer.finish())
+}
+
+/// Frames the data in blocks, with restart markers holding file offsets.
+fn run_block_transfer(
+    state: &SessionState,
+    read_stream: &mut dyn Read,
+    write_stream: &mut dyn Write,
+) -> (Status, String) {
+    let position = Cell::new(state.file_offset as u64);
+    let mut reader = BlockReader::new(read_stream);
+    let mut writer = BlockWriter::new(write_stream, &position, config::restart_marker_interval());
+
+    let result = run_translated_transfer(state, &mut reader, &mut writer, Some(&position));
+    finish_download(state, result, || writer.finish())
+}
+
+/// Ends the framing around a completed download with `finish`. Uploads are
+/// ended by the client, nothing may be sent back on their connection.
+fn finish_download(
+    state: &SessionState,
+    result: (Status, String),
+    finish: impl FnOnce() -> std::io::Result<()>,
+) -> (Status, String) {
+    if result.0 != 226 || state.data_transfer_upload {
+        return result;
+    }
 
-        state.data_socket = socket;
-    }
-
-    let result = match state.data_socket {
-        None => return (425, "Error accepting data connection.".to_string()),
-        Some(ref mut socket) => {
-            let mut read_stream = socket.try_clone().unwrap();
-            let mut write_stream = socket.try_clone().unwrap();
-            state.data_transfer_func.as_ref().unwrap()(
-                &state
-                    .data_transfer_func_parameter
-                    .as_ref()
-                    .unwrap_or(&String::new()),
-                state.file_offset,
-                Some(&mut read_stream),
-                Some(&mut write_stream),
-            )
+    match finish() {
+        Ok(()) => result,
+        Err(error) => {
+            warn!("Error ending transfer: {}", error);
+            (426, "Error while sending data.".to_string())
         }
-    };
+    }
+}
 
-    if let Some(ref stream) = state.data_socket {
-        stream.shutdown(std::net::Shutdown::Both).unwrap();
-        state.data_socket = None;
+/// Translates file contents to and from ASCII or EBCDIC unless the session
+/// is in binary mode. `position` follows the file offset, when needed.
+fn run_translated_transfer(
+    state: &SessionState,
+    read_stream: &mut dyn Read,
+    write_stream: &mut dyn Write,
+    position: Option<&Cell<u64>>,
+) -> (Status, String) {
+    if state.data_transfer_translated && state.structure == FileStructure::Record {
+        return run_record_transfer(state, read_stream, write_stream, position);
+    }
+    if !state.data_transfer_translated || state.binary_flag {
+        return call_transfer_func(state, read_stream, write_stream, position);
     }
 
-    state.data_listener = None;
-    state.data_transfer_func = None;
-    state.data_transfer_func_parameter = None;
+    match state.ebcdic {
+        Some(code_page) => call_transfer_func(
+            state,
+            &mut EbcdicReader::new(read_stream, code_page),
+            &mut EbcdicWriter::new(write_stream, code_page),
+            position,
+        ),
+        None => call_transfer_func(
+            state,
+            &mut AsciiReader::new(read_stream),
+            &mut AsciiWriter::new(write_stream),
+            position,
+        ),
+    }
+}
+
+/// Sends the lines of a file as records, and receives records as lines. The
+/// records hold no line ends, so only EBCDIC needs translating.
+fn run_record_transfer(
+    state: &SessionState,
+    read_stream: &mut dyn Read,
+    write_stream: &mut dyn Write,
+    position: Option<&Cell<u64>>,
+) -> (Status, String) {
+    let code_page = state.ebcdic.filter(|_| !state.binary_flag);
+    let mut reader = RecordReader::new(read_stream, code_page);
+    let mut writer = RecordWriter::new(write_stream, code_page);
+
+    let result = call_transfer_func(state, &mut reader, &mut writer, position);
+    finish_download(state, result, || writer.finish())
+}
+
+fn call_transfer_func(
+    state: &SessionState,
+    read_stream: &mut dyn Read,
+    write_stream: &mut dyn Write,
+    position: Option<&Cell<u64>>,
+) -> (Status, String) {
+    let mut counter;
+    let write_stream: &mut dyn Write = match position {
+        Some(position) => {
+            counter = Counter::new(write_stream, position);
+            &mut counter
+        }
+        None => write_stream,
+    };
+
+    state.data_transfer_func.as_ref().unwrap()(
+        state
+            .data_transfer_func_parameter
+            .as_ref()
+            .unwrap_or(&String::new()),
+        state.file_offset,
+        Some(read_stream),
+        Some(write_stream),
+    )
+}
+
+fn run_socket_transfer(state: &SessionState, socket: &TcpStream) -> (Status, String) {
+    state.data_socket_transfer_func.unwrap()(
+        state
+            .data_transfer_func_parameter
+            .as_deref()
+            .unwrap_or_default(),
+        state.file_offset,
+        socket,
+    )
+}
+
+/// Runs the transfer over TLS, as requested with PROT P.
+fn run_protected_transfer(
+    state: &SessionState,
+    socket: &TcpStream,
+    config: Option<Arc<ServerConfig>>,
+) -> (Status, String) {
+    let stream = match config {
+        Some(config) => TlsStream::accept(config, socket.try_clone().unwrap()),
+        None => return (425, "TLS is not available.".to_string()),
+    };
+
+    let stream = match stream {
+        Ok(stream) => stream,
+        Err(error) => {
+            warn!("TLS handshake on data connection failed: {}", error);
+            return (425, "TLS negotiation failed.".to_string());
+        }
+    };
+
+    let result = run_transfer(state, &mut stream.clone(), &mut stream.clone());
+
+    if let Err(error) = stream.close() {
+        warn!("Error closing TLS data connection: {}", error);
+    }
 
     result
 }
@@ -216,7 +505,7 @@ fn end_session(stream: &mut TcpStream) {
 #[cfg(test)]
 mod tests {
 
-    use crate::command::verb::Verb;
+    use crate::{command::verb::Verb, testing::test_dir};
 
     use super::*;
 
@@ -291,7 +580,7 @@ mod tests {
         let mut session = Session::new(stream);
         let result = handle_pass(&mut session);
         assert_eq!(result, ShouldExit::No);
-        assert_eq!(session.state.has_greeted, true);
+        assert!(session.state.has_greeted);
     }
 
     #[test]
@@ -393,7 +682,7 @@ mod tests {
         let ((status, msg), new_state) = run_command(&command, &state);
 
         assert_eq!(new_state.user, Some("foo".to_string()));
-        assert_eq!(new_state.is_authenticated, false);
+        assert!(!new_state.is_authenticated);
         assert_eq!(new_state.previous_command, Some(verb));
         assert_eq!(status, 331);
         assert!(msg != "");
@@ -407,6 +696,19 @@ mod tests {
         assert_eq!(stream.out, b"200 foobar\r\n");
     }
 
+    #[test]
+    fn write_result_multiline() {
+        let mut stream = MockStream::default();
+        let res = write_result_to_peer(&mut stream, 211, "foo\n bar\nbaz");
+        assert_eq!(res, ShouldExit::No);
+        assert_eq!(stream.out, b"211-foo\r\n211- bar\r\n211 baz\r\n");
+
+        let mut stream = MockStream::default();
+        let res = write_feat_to_peer(&mut stream, 211, "foo\n bar\nbaz");
+        assert_eq!(res, ShouldExit::No);
+        assert_eq!(stream.out, b"211-foo\r\n bar\r\n211 baz\r\n");
+    }
+
     #[test]
     fn write_result_error() {
         let mut stream = MockErrorStream {};
@@ -434,4 +736,304 @@ mod tests {
         end_session(&mut stream);
         assert!(stream.write(msg).is_err());
     }
+
+    fn send_foo(
+        _: &str,
+        _: usize,
+        _: Option<&mut dyn Read>,
+        write_stream: Option<&mut dyn Write>,
+    ) -> (Status, String) {
+        write_stream.unwrap().write_all(b"foo").unwrap();
+        (226, "Transfer complete.".to_string())
+    }
+
+    #[test]
+    fn protected_transfer() {
+        let config = tls::load_server_config(&tls::tests::test_cert(), &tls::tests::test_key());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = std::thread::spawn(move || {
+            let mut client = tls::tests::test_client(TcpStream::connect(address).unwrap());
+            let mut received = vec![];
+            client.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let state = SessionState {
+            data_transfer_func: Some(send_foo),
+            ..Default::default()
+        };
+        let (socket, _) = listener.accept().unwrap();
+        let (status, _) = run_protected_transfer(&state, &socket, Some(Arc::new(config.unwrap())));
+        socket.shutdown(std::net::Shutdown::Both).unwrap();
+
+        assert_eq!(status, 226);
+        assert_eq!(client.join().unwrap(), b"foo");
+    }
+
+    #[test]
+    fn protected_transfer_without_tls() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let state = SessionState::default();
+        let (status, msg) = run_protected_transfer(&state, &socket, None);
+        assert_eq!(status, 425);
+        assert_eq!(msg, "TLS is not available.");
+    }
+
+    #[test]
+    fn passive_transfer_waits_for_client() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut state = SessionState {
+            peer_ip: address.ip(),
+            data_listener: Some(listener),
+            data_transfer_func: Some(send_foo),
+            ..Default::default()
+        };
+
+        let client = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            let mut received = vec![];
+            TcpStream::connect(address)
+                .unwrap()
+                .read_to_end(&mut received)
+                .unwrap();
+            received
+        });
+
+        let (status, _) = process_data_request(&mut state);
+        assert_eq!(status, 226);
+        assert_eq!(client.join().unwrap(), b"foo");
+        assert!(state.data_listener.is_none());
+        assert!(state.data_transfer_func.is_none());
+    }
+
+    #[test]
+    fn stou_without_data_connection_releases_name() {
+        let dir = test_dir("session", "stou");
+        let mut state = SessionState::default();
+        state.log_in(crate::auth::Identity {
+            display_name: "foo".to_string(),
+            home: dir.clone(),
+            permissions: crate::auth::Permissions::from_flags("rw"),
+        });
+
+        let command = command::parse(b"STOU upload\r\n").unwrap();
+        let ((status, _), mut state) = run_command(&command, &state);
+        assert_eq!(status, 150);
+        let file = state.data_transfer_func_parameter.clone().unwrap();
+        assert!(std::path::Path::new(&file).exists());
+
+        let (status, _) = process_data_request(&mut state);
+        assert_eq!(status, 425);
+        assert!(!std::path::Path::new(&file).exists());
+        assert!(state.data_transfer_cleanup.is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stou_failed_transfer_releases_name() {
+        let dir = test_dir("session", "stou-failed");
+        let mut state = SessionState::default();
+        state.log_in(crate::auth::Identity {
+            display_name: "foo".to_string(),
+            home: dir.clone(),
+            permissions: crate::auth::Permissions::from_flags("rw"),
+        });
+
+        let command = command::parse(b"STOU upload\r\n").unwrap();
+        let ((status, _), mut state) = run_command(&command, &state);
+        assert_eq!(status, 150);
+        let file = state.data_transfer_func_parameter.clone().unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        state.peer_ip = address.ip();
+        state.data_listener = Some(listener);
+
+        // The client resets the connection in the middle of the upload.
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            socket2::SockRef::from(&stream)
+                .set_linger(Some(std::time::Duration::ZERO))
+                .unwrap();
+            stream.write_all(b"partial").unwrap();
+        });
+
+        let (status, _) = process_data_request(&mut state);
+        client.join().unwrap();
+        assert_eq!(status, 426);
+        assert!(!std::path::Path::new(&file).exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn echo(
+        _: &str,
+        _: usize,
+        read_stream: Option<&mut dyn Read>,
+        write_stream: Option<&mut dyn Write>,
+    ) -> (Status, String) {
+        let mut received = vec![];
+        read_stream.unwrap().read_to_end(&mut received).unwrap();
+        write_stream.unwrap().write_all(&received).unwrap();
+        (226, "Transfer complete.".to_string())
+    }
+
+    #[test]
+    fn translated_transfer() {
+        let mut state = SessionState {
+            data_transfer_func: Some(echo),
+            data_transfer_translated: true,
+            ..Default::default()
+        };
+        let mut out = MockStream::default();
+        run_transfer(&state, &mut "a\r\nb\n".as_bytes(), &mut out);
+        assert_eq!(out.out, b"a\r\nb\r\n");
+
+        state.ebcdic = Some(ebcdic::CodePage::Cp037);
+        let mut out = MockStream::default();
+        run_transfer(&state, &mut [0x81, 0x15].as_slice(), &mut out);
+        assert_eq!(out.out, [0x81, 0x15]);
+
+        state.binary_flag = true;
+        let mut out = MockStream::default();
+        run_transfer(&state, &mut "a\r\nb\n".as_bytes(), &mut out);
+        assert_eq!(out.out, b"a\r\nb\n");
+    }
+
+    #[test]
+    fn block_mode_transfer() {
+        let mut state = SessionState {
+            data_transfer_func: Some(send_foo),
+            transfer_mode: TransferMode::Block,
+            ..Default::default()
+        };
+        let mut out = MockStream::default();
+        let (status, _) = run_transfer(&state, &mut "".as_bytes(), &mut out);
+        assert_eq!(status, 226);
+        assert_eq!(out.out, b"\0\0\x03foo\x40\0\0");
+
+        // Uploads skip restart markers and get no end of file block back.
+        state.data_transfer_func = Some(echo);
+        state.data_transfer_upload = true;
+        let mut out = MockStream::default();
+        let input = b"\0\0\x01b\x10\0\x011\x40\0\x02ar";
+        let (status, _) = run_transfer(&state, &mut input.as_slice(), &mut out);
+        assert_eq!(status, 226);
+        assert_eq!(out.out, b"\0\0\x03bar");
+    }
+
+    #[test]
+    fn deflate_mode_transfer() {
+        let mut state = SessionState {
+            data_transfer_func: Some(send_foo),
+            transfer_mode: TransferMode::Deflate,
+            ..Default::default()
+        };
+        let mut out = MockStream::default();
+        let (status, _) = run_transfer(&state, &mut "".as_bytes(), &mut out);
+        assert_eq!(status, 226);
+        let mut received = String::new();
+        ZlibDecoder::new(out.out.as_slice())
+            .read_to_string(&mut received)
+            .unwrap();
+        assert_eq!(received, "foo");
+
+        // Uploads are decompressed, and translated from ASCII afterwards.
+        fn receive(
+            _: &str,
+            _: usize,
+            read_stream: Option<&mut dyn Read>,
+            _: Option<&mut dyn Write>,
+        ) -> (Status, String) {
+            let mut received = String::new();
+            read_stream.unwrap().read_to_string(&mut received).unwrap();
+            (226, received)
+        }
+
+        state.data_transfer_func = Some(receive);
+        state.data_transfer_translated = true;
+        state.data_transfer_upload = true;
+        let mut input = vec![];
+        let mut writer = DeflateWriter::new(&mut input, 9);
+        writer.write_all(b"bar\r\n").unwrap();
+        writer.finish().unwrap();
+
+        let mut out = MockStream::default();
+        let (status, received) = run_transfer(&state, &mut input.as_slice(), &mut out);
+        assert_eq!(status, 226);
+        assert_eq!(received, "bar\n");
+        assert!(out.out.is_empty());
+
+        // Nothing is sent back either when the client closes without data.
+        fn store_nothing(
+            _: &str,
+            _: usize,
+            _: Option<&mut dyn Read>,
+            _: Option<&mut dyn Write>,
+        ) -> (Status, String) {
+            (226, "Transfer complete.".to_string())
+        }
+
+        state.data_transfer_func = Some(store_nothing);
+        let mut out = MockStream::default();
+        let (status, _) = run_transfer(&state, &mut "".as_bytes(), &mut out);
+        assert_eq!(status, 226);
+        assert!(out.out.is_empty());
+    }
+
+    #[test]
+    fn record_structure_transfer() {
+        let mut state = SessionState {
+            data_transfer_func: Some(send_foo),
+            data_transfer_translated: true,
+            structure: FileStructure::Record,
+            ..Default::default()
+        };
+        let mut out = MockStream::default();
+        let (status, _) = run_transfer(&state, &mut "".as_bytes(), &mut out);
+        assert_eq!(status, 226);
+        assert_eq!(out.out, b"foo\xff\x02");
+
+        // Uploads get records back as lines, without an end of file code.
+        state.data_transfer_func = Some(echo);
+        state.data_transfer_upload = true;
+        state.ebcdic = Some(ebcdic::CodePage::Cp037);
+        let mut out = MockStream::default();
+        let input = b"\xc1\xff\x01\xc2\xff\x03";
+        let (status, _) = run_transfer(&state, &mut input.as_slice(), &mut out);
+        assert_eq!(status, 226);
+        assert_eq!(out.out, b"\xc1\xff\x01\xc2\xff\x01");
+
+        // Listings keep their line ends.
+        state.data_transfer_func = Some(send_foo);
+        state.data_transfer_translated = false;
+        state.data_transfer_upload = false;
+        let mut out = MockStream::default();
+        run_transfer(&state, &mut "".as_bytes(), &mut out);
+        assert_eq!(out.out, b"foo");
+    }
+
+    #[test]
+    fn active_connection_refused() {
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let mut state = SessionState {
+            port_ip: Some(address),
+            data_transfer_func: Some(send_foo),
+            file_offset: 10,
+            ..Default::default()
+        };
+
+        let (status, msg) = process_data_request(&mut state);
+        assert_eq!(status, 425);
+        assert_eq!(msg, "Can't open data connection.");
+        assert!(state.data_transfer_func.is_none());
+        assert_eq!(state.file_offset, 0);
+    }
 }
@@ -0,0 +1,277 @@
+use std::{
+    cell::Cell,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use log::debug;
+
+// Block descriptors from RFC 959, section 3.4.2.
+const END_OF_RECORD: u8 = 0x80;
+const END_OF_FILE: u8 = 0x40;
+const RESTART_MARKER: u8 = 0x10;
+
+const MAX_BLOCK_SIZE: usize = u16::MAX as usize;
+
+/// Sends data in block mode (MODE B). After every `interval` bytes of the
+/// file a restart marker is sent, holding the file offset reached so far,
+/// which the client can pass to REST to resume from there.
+pub(super) struct BlockWriter<'a> {
+    inner: &'a mut dyn Write,
+    /// File offset of the data written so far, kept up to date by a
+    /// [`Counter`] in front of any translation.
+    position: &'a Cell<u64>,
+    interval: u64,
+    next_marker: u64,
+    buffer: Vec<u8>,
+}
+
+impl<'a> BlockWriter<'a> {
+    pub(super) fn new(inner: &'a mut dyn Write, position: &'a Cell<u64>, interval: u64) -> Self {
+        Self {
+            inner,
+            position,
+            interval,
+            next_marker: position.get() + interval,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Tells the client that the whole file has been sent.
+    pub(super) fn finish(&mut self) -> io::Result<()> {
+        self.inner.write_all(&[END_OF_FILE, 0, 0])?;
+        self.inner.flush()
+    }
+}
+
+impl Write for BlockWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        for chunk in buf.chunks(MAX_BLOCK_SIZE) {
+            push_block(&mut self.buffer, 0, chunk);
+        }
+
+        let position = self.position.get();
+        if position >= self.next_marker {
+            push_block(
+                &mut self.buffer,
+                RESTART_MARKER,
+                position.to_string().as_bytes(),
+            );
+            self.next_marker = position + self.interval;
+        }
+
+        self.inner.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn push_block(buffer: &mut Vec<u8>, descriptor: u8, data: &[u8]) {
+    buffer.push(descriptor);
+    buffer.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(data);
+}
+
+/// Receives data sent in block mode, up to the block marked as end of file.
+/// Restart markers from the client are skipped without a `110` reply. An
+/// upload that fails is discarded as a whole, so the server has no offset to
+/// pair a marker with, and marker restart only works for downloads.
+pub(super) struct BlockReader<'a> {
+    inner: &'a mut dyn Read,
+    /// Data left in the current block.
+    remaining: usize,
+    last_block: bool,
+}
+
+impl<'a> BlockReader<'a> {
+    pub(super) fn new(inner: &'a mut dyn Read) -> Self {
+        Self {
+            inner,
+            remaining: 0,
+            last_block: false,
+        }
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let mut header = [0; 3];
+        self.inner.read_exact(&mut header)?;
+        let count = u16::from_be_bytes([header[1], header[2]]) as usize;
+
+        if header[0] & RESTART_MARKER != 0 {
+            let mut marker = vec![0; count];
+            self.inner.read_exact(&mut marker)?;
+            debug!("Restart marker {}", String::from_utf8_lossy(&marker));
+        } else {
+            self.remaining = count;
+        }
+
+        if header[0] & END_OF_RECORD != 0 {
+            debug!("End of record");
+        }
+        self.last_block = header[0] & END_OF_FILE != 0;
+        Ok(())
+    }
+}
+
+impl Read for BlockReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            if self.last_block || buf.is_empty() {
+                return Ok(0);
+            }
+            self.read_header().map_err(|error| match error.kind() {
+                ErrorKind::UnexpectedEof => io::Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Connection closed before end of file",
+                ),
+                _ => error,
+            })?;
+        }
+
+        let limit = buf.len().min(self.remaining);
+        let count = self.inner.read(&mut buf[..limit])?;
+        if count == 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed inside a block",
+            ));
+        }
+
+        self.remaining -= count;
+        Ok(count)
+    }
+}
+
+/// Adds the bytes written through it to `count`, before passing them on.
+pub(super) struct Counter<'a> {
+    inner: &'a mut dyn Write,
+    count: &'a Cell<u64>,
+}
+
+impl<'a> Counter<'a> {
+    pub(super) fn new(inner: &'a mut dyn Write, count: &'a Cell<u64>) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl Write for Counter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Counted first, so that the layers below see the offset this data
+        // ends at.
+        self.count.set(self.count.get() + buf.len() as u64);
+        self.inner.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_blocks_with_markers() {
+        let position = Cell::new(100);
+        let mut out = vec![];
+        let mut writer = BlockWriter::new(&mut out, &position, 4);
+        for chunk in [&b"abc"[..], b"de", b""] {
+            Counter::new(&mut writer, &position)
+                .write_all(chunk)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut expected = vec![0, 0, 3];
+        expected.extend_from_slice(b"abc");
+        expected.extend_from_slice(&[0, 0, 2]);
+        expected.extend_from_slice(b"de");
+        expected.extend_from_slice(&[RESTART_MARKER, 0, 3]);
+        expected.extend_from_slice(b"105");
+        expected.extend_from_slice(&[END_OF_FILE, 0, 0]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn split_large_writes() {
+        let position = Cell::new(0);
+        let mut out = vec![];
+        let data = vec![1u8; MAX_BLOCK_SIZE + 10];
+        BlockWriter::new(&mut out, &position, u64::MAX)
+            .write_all(&data)
+            .unwrap();
+        assert_eq!(out.len(), data.len() + 6);
+        assert_eq!(out[..3], [0, 0xff, 0xff]);
+        assert_eq!(out[MAX_BLOCK_SIZE + 3..MAX_BLOCK_SIZE + 6], [0, 0, 10]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..=255).cycle().take(200_000).collect();
+        let position = Cell::new(0);
+        let mut encoded = vec![];
+        let mut writer = BlockWriter::new(&mut encoded, &position, 50_000);
+        for chunk in data.chunks(30_000) {
+            Counter::new(&mut writer, &position)
+                .write_all(chunk)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        // Anything after the end of file block is not part of the file.
+        encoded.extend_from_slice(b"garbage");
+
+        let mut decoded = vec![];
+        let mut input = encoded.as_slice();
+        BlockReader::new(&mut input)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn skip_markers_in_upload() {
+        let mut input = vec![0, 0, 3];
+        input.extend_from_slice(b"foo");
+        input.extend_from_slice(&[RESTART_MARKER, 0, 4]);
+        input.extend_from_slice(b"mark");
+        input.extend_from_slice(&[END_OF_FILE, 0, 3]);
+        input.extend_from_slice(b"bar");
+
+        let mut decoded = vec![];
+        BlockReader::new(&mut input.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"foobar");
+    }
+
+    #[test]
+    fn read_truncated() {
+        let mut input: &[u8] = &[0, 0, 5, b'a', b'b'];
+        let mut decoded = vec![];
+        let error = BlockReader::new(&mut input)
+            .read_to_end(&mut decoded)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+
+        let mut input: &[u8] = &[0, 0, 1, b'a'];
+        let error = BlockReader::new(&mut input)
+            .read_to_end(&mut decoded)
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_eof_with_data() {
+        let mut input: &[u8] = &[END_OF_RECORD | END_OF_FILE, 0, 2, b'h', b'i'];
+        let mut decoded = vec![];
+        BlockReader::new(&mut input)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"hi");
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{IpAddr, TcpListener, TcpStream},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use socket2::SockRef;
+
+/// Waits up to `timeout` for a client to connect to a passive `listener`.
+/// Connections from other hosts than `control_peer` are dropped unless
+/// `allow_foreign` accepts them, and the wait goes on, so that nobody can
+/// steal the transfer by guessing the port.
+pub(super) fn accept(
+    listener: &TcpListener,
+    timeout: Duration,
+    control_peer: IpAddr,
+    allow_foreign: &dyn Fn(IpAddr) -> bool,
+) -> io::Result<TcpStream> {
+    let deadline = Instant::now() + timeout;
+    listener.set_nonblocking(false)?;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(timed_out());
+        }
+
+        // A receive timeout on a listening socket bounds how long accept blocks.
+        SockRef::from(listener).set_read_timeout(Some(remaining))?;
+
+        match listener.accept() {
+            Ok((stream, address)) => {
+                let peer = address.ip().to_canonical();
+                if peer != control_peer && !allow_foreign(peer) {
+                    warn!(
+                        "Rejected data connection from {}, expected {}",
+                        address, control_peer
+                    );
+                    continue;
+                }
+
+                stream.set_read_timeout(None)?;
+                return Ok(stream);
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => return Err(timed_out()),
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(ErrorKind::TimedOut, "Timed out waiting for data connection")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, thread};
+
+    use socket2::{Domain, Socket, Type};
+
+    use super::*;
+
+    const LOCALHOST: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    #[test]
+    fn accept_late_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            TcpStream::connect(address).unwrap()
+        });
+
+        let stream = accept(&listener, Duration::from_secs(5), LOCALHOST, &|_| false).unwrap();
+        let client = client.join().unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), client.local_addr().unwrap());
+    }
+
+    #[test]
+    fn accept_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let start = Instant::now();
+        let error =
+            accept(&listener, Duration::from_millis(100), LOCALHOST, &|_| false).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn reject_foreign_peer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let _intruder = TcpStream::connect(address).unwrap();
+
+        let expected = "192.0.2.1".parse().unwrap();
+        let error =
+            accept(&listener, Duration::from_millis(100), expected, &|_| false).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn accept_allowed_foreign_peer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = TcpStream::connect(address).unwrap();
+
+        let control_peer = "192.0.2.1".parse().unwrap();
+        let allow = |host: IpAddr| host == LOCALHOST;
+        let stream = accept(&listener, Duration::from_secs(5), control_peer, &allow).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), client.local_addr().unwrap());
+    }
+
+    #[test]
+    fn keep_waiting_for_expected_peer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let intruder = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+        let source: SocketAddr = "127.0.0.2:0".parse().unwrap();
+        intruder.bind(&source.into()).unwrap();
+        intruder.connect(&address.into()).unwrap();
+
+        let client = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            TcpStream::connect(address).unwrap()
+        });
+
+        let stream = accept(&listener, Duration::from_secs(5), LOCALHOST, &|_| false).unwrap();
+        let client = client.join().unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), client.local_addr().unwrap());
+    }
+}
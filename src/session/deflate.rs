@@ -0,0 +1,129 @@
+use std::io::{self, Write};
+
+use flate2::{Compress, Compression, FlushCompress, Status};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Compresses data sent in deflate mode (MODE Z) into a zlib stream. The
+/// stream is only ended by [`DeflateWriter::finish`], so nothing is sent if
+/// the writer is never used.
+pub(super) struct DeflateWriter<'a> {
+    inner: &'a mut dyn Write,
+    compress: Compress,
+    buffer: Vec<u8>,
+}
+
+impl<'a> DeflateWriter<'a> {
+    pub(super) fn new(inner: &'a mut dyn Write, level: u32) -> Self {
+        Self {
+            inner,
+            compress: Compress::new(Compression::new(level), true),
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+        }
+    }
+
+    /// Sends the rest of the compressed data and the end of the stream.
+    pub(super) fn finish(&mut self) -> io::Result<()> {
+        loop {
+            self.buffer.clear();
+            let status = self
+                .compress
+                .compress_vec(&[], &mut self.buffer, FlushCompress::Finish)
+                .map_err(io::Error::other)?;
+            self.inner.write_all(&self.buffer)?;
+
+            if status == Status::StreamEnd {
+                return self.inner.flush();
+            }
+        }
+    }
+}
+
+impl Write for DeflateWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut input = buf;
+
+        while !input.is_empty() {
+            self.buffer.clear();
+            let before = self.compress.total_in();
+            self.compress
+                .compress_vec(input, &mut self.buffer, FlushCompress::None)
+                .map_err(io::Error::other)?;
+            input = &input[(self.compress.total_in() - before) as usize..];
+            self.inner.write_all(&self.buffer)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::*;
+
+    fn compress(data: &[u8], level: u32) -> Vec<u8> {
+        let mut out = vec![];
+        let mut writer = DeflateWriter::new(&mut out, level);
+        for chunk in data.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = b"Lorem ipsum dolor sit amet.\n".repeat(20_000);
+        for level in [0, 1, 6, 9] {
+            let compressed = compress(&text, level);
+            let mut decoded = vec![];
+            ZlibDecoder::new(compressed.as_slice())
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, text);
+        }
+    }
+
+    #[test]
+    fn compression_levels() {
+        let text = b"Lorem ipsum dolor sit amet.\n".repeat(20_000);
+        assert!(compress(&text, 0).len() > text.len());
+        assert!(compress(&text, 9).len() < text.len() / 10);
+    }
+
+    #[test]
+    fn empty_stream() {
+        let compressed = compress(b"", 6);
+        assert!(!compressed.is_empty());
+        let mut decoded = vec![];
+        ZlibDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    fn unused_writer_sends_nothing() {
+        let mut out = vec![];
+        drop(DeflateWriter::new(&mut out, 6));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn truncated_stream_is_an_error() {
+        let text = b"Lorem ipsum dolor sit amet.\n".repeat(1000);
+        let compressed = compress(&text, 6);
+        let mut decoded = vec![];
+        let result =
+            ZlibDecoder::new(&compressed[..compressed.len() / 2]).read_to_end(&mut decoded);
+        assert!(result.is_err());
+    }
+}
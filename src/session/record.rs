@@ -0,0 +1,229 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+
+use super::ebcdic::CodePage;
+
+// Control codes of record structure in stream mode, RFC 959 section 3.4.1.
+const ESCAPE: u8 = 0xff;
+const END_OF_RECORD: u8 = 0x01;
+const END_OF_FILE: u8 = 0x02;
+const END_OF_RECORD_AND_FILE: u8 = 0x03;
+
+/// Sends a file with record structure (STRU R), each line of the file being
+/// one record. Record contents are sent in EBCDIC if `code_page` is set and
+/// as they are otherwise, since they hold no line ends to translate.
+pub(super) struct RecordWriter<'a> {
+    inner: &'a mut dyn Write,
+    code_page: Option<CodePage>,
+    buffer: Vec<u8>,
+}
+
+impl<'a> RecordWriter<'a> {
+    pub(super) fn new(inner: &'a mut dyn Write, code_page: Option<CodePage>) -> Self {
+        Self {
+            inner,
+            code_page,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Marks the end of the file. A last line without a newline is sent as
+    /// an unterminated record, so that it comes back the same way.
+    pub(super) fn finish(&mut self) -> io::Result<()> {
+        self.inner.write_all(&[ESCAPE, END_OF_FILE])?;
+        self.inner.flush()
+    }
+}
+
+impl Write for RecordWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        for &byte in buf {
+            if byte == b'\n' {
+                self.buffer.extend_from_slice(&[ESCAPE, END_OF_RECORD]);
+                continue;
+            }
+
+            let byte = match self.code_page {
+                Some(code_page) => code_page.encode(byte),
+                None => byte,
+            };
+            if byte == ESCAPE {
+                self.buffer.push(ESCAPE);
+            }
+            self.buffer.push(byte);
+        }
+
+        self.inner.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Receives a file with record structure, writing each record as a line.
+/// The file ends with its end of file code, or when the connection closes.
+pub(super) struct RecordReader<'a> {
+    inner: BufReader<&'a mut dyn Read>,
+    code_page: Option<CodePage>,
+    /// An escape ended the last input, its meaning depends on the next byte.
+    pending_escape: bool,
+    done: bool,
+}
+
+impl<'a> RecordReader<'a> {
+    pub(super) fn new(inner: &'a mut dyn Read, code_page: Option<CodePage>) -> Self {
+        Self {
+            inner: BufReader::new(inner),
+            code_page,
+            pending_escape: false,
+            done: false,
+        }
+    }
+}
+
+impl Read for RecordReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let code_page = self.code_page;
+        let decode = |byte| match code_page {
+            Some(code_page) => code_page.decode(byte),
+            None => byte,
+        };
+        let mut count = 0;
+
+        while count == 0 && !buf.is_empty() && !self.done {
+            let input = self.inner.fill_buf()?;
+            if input.is_empty() {
+                if self.pending_escape {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "Connection closed after an escape",
+                    ));
+                }
+                break;
+            }
+
+            let mut used = 0;
+            while used < input.len() && count < buf.len() && !self.done {
+                let byte = input[used];
+                used += 1;
+
+                if !self.pending_escape {
+                    match byte {
+                        ESCAPE => self.pending_escape = true,
+                        _ => {
+                            buf[count] = decode(byte);
+                            count += 1;
+                        }
+                    }
+                    continue;
+                }
+
+                self.pending_escape = false;
+                buf[count] = match byte {
+                    ESCAPE => decode(ESCAPE),
+                    END_OF_RECORD => b'\n',
+                    END_OF_RECORD_AND_FILE => {
+                        self.done = true;
+                        b'\n'
+                    }
+                    END_OF_FILE => {
+                        self.done = true;
+                        continue;
+                    }
+                    _ => {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Invalid control code {:#04x}", byte),
+                        ))
+                    }
+                };
+                count += 1;
+            }
+
+            self.inner.consume(used);
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(data: &[u8], code_page: Option<CodePage>) -> Vec<u8> {
+        let mut out = vec![];
+        let mut writer = RecordWriter::new(&mut out, code_page);
+        writer.write_all(data).unwrap();
+        writer.finish().unwrap();
+        out
+    }
+
+    fn decode(data: &[u8], code_page: Option<CodePage>) -> io::Result<Vec<u8>> {
+        let mut input = data;
+        let mut out = vec![];
+        RecordReader::new(&mut input, code_page).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn lines_to_records() {
+        assert_eq!(
+            encode(b"ab\n\ncd\xff\n", None),
+            b"ab\xff\x01\xff\x01cd\xff\xff\xff\x01\xff\x02"
+        );
+        assert_eq!(encode(b"ab", None), b"ab\xff\x02");
+        assert_eq!(
+            encode(b"A\n", Some(CodePage::Cp037)),
+            b"\xc1\xff\x01\xff\x02"
+        );
+    }
+
+    #[test]
+    fn records_to_lines() {
+        assert_eq!(
+            decode(b"ab\xff\x01\xff\x01cd\xff\xff\xff\x03trailing", None).unwrap(),
+            b"ab\n\ncd\xff\n"
+        );
+        assert_eq!(decode(b"ab\xff\x02", None).unwrap(), b"ab");
+        assert_eq!(decode(b"ab\xff\x01", None).unwrap(), b"ab\n");
+        assert_eq!(
+            decode(b"\xc1\xff\x01", Some(CodePage::Cp037)).unwrap(),
+            b"A\n"
+        );
+    }
+
+    #[test]
+    fn invalid_records() {
+        assert_eq!(
+            decode(b"ab\xff", None).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            decode(b"ab\xff\x04", None).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        for code_page in [None, Some(CodePage::Cp037), Some(CodePage::Cp1047)] {
+            let encoded = encode(&data, code_page);
+            assert_eq!(decode(&encoded, code_page).unwrap(), data);
+        }
+
+        // Escapes split over reads.
+        let encoded = encode(&data, None);
+        let mut input = encoded.as_slice();
+        let mut reader = RecordReader::new(&mut input, None);
+        let mut out = vec![];
+        let mut byte = [0];
+        while reader.read(&mut byte).unwrap() == 1 {
+            out.push(byte[0]);
+        }
+        assert_eq!(out, data);
+    }
+}
@@ -0,0 +1,264 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use super::ebcdic::CodePage;
+
+/// Sends text in the ASCII representation (TYPE A), turning every LF of the
+/// local file into the CRLF used on the network.
+pub(super) struct AsciiWriter<'a> {
+    inner: &'a mut dyn Write,
+    buffer: Vec<u8>,
+}
+
+impl<'a> AsciiWriter<'a> {
+    pub(super) fn new(inner: &'a mut dyn Write) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Write for AsciiWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        for &byte in buf {
+            if byte == b'\n' {
+                self.buffer.push(b'\r');
+            }
+            self.buffer.push(byte);
+        }
+
+        self.inner.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Receives text in the ASCII representation, turning every CRLF into the
+/// LF used locally. Other CRs are kept, so that translating back gives the
+/// bytes that were received.
+pub(super) struct AsciiReader<'a> {
+    inner: BufReader<&'a mut dyn Read>,
+    /// A CR ended the last input, it is held back until it is known whether
+    /// a LF follows.
+    pending_cr: bool,
+}
+
+impl<'a> AsciiReader<'a> {
+    pub(super) fn new(inner: &'a mut dyn Read) -> Self {
+        Self {
+            inner: BufReader::new(inner),
+            pending_cr: false,
+        }
+    }
+}
+
+impl Read for AsciiReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut count = 0;
+
+        while count == 0 && !buf.is_empty() {
+            let input = self.inner.fill_buf()?;
+            if input.is_empty() {
+                if self.pending_cr {
+                    self.pending_cr = false;
+                    buf[0] = b'\r';
+                    count = 1;
+                }
+                break;
+            }
+
+            let mut used = 0;
+            while used < input.len() && count < buf.len() {
+                let byte = input[used];
+                if self.pending_cr {
+                    // A lone CR is data, the byte after it is handled next round.
+                    self.pending_cr = false;
+                    buf[count] = match byte {
+                        b'\n' => {
+                            used += 1;
+                            b'\n'
+                        }
+                        _ => b'\r',
+                    };
+                    count += 1;
+                    continue;
+                }
+
+                used += 1;
+                match byte {
+                    b'\r' => self.pending_cr = true,
+                    _ => {
+                        buf[count] = byte;
+                        count += 1;
+                    }
+                }
+            }
+
+            self.inner.consume(used);
+        }
+
+        Ok(count)
+    }
+}
+
+/// Sends text in the EBCDIC representation (TYPE E) using `code_page`.
+pub(super) struct EbcdicWriter<'a> {
+    inner: &'a mut dyn Write,
+    code_page: CodePage,
+    buffer: Vec<u8>,
+}
+
+impl<'a> EbcdicWriter<'a> {
+    pub(super) fn new(inner: &'a mut dyn Write, code_page: CodePage) -> Self {
+        Self {
+            inner,
+            code_page,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Write for EbcdicWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        self.buffer
+            .extend(buf.iter().map(|&byte| self.code_page.encode(byte)));
+
+        self.inner.write_all(&self.buffer)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Receives text in the EBCDIC representation using `code_page`.
+pub(super) struct EbcdicReader<'a> {
+    inner: &'a mut dyn Read,
+    code_page: CodePage,
+}
+
+impl<'a> EbcdicReader<'a> {
+    pub(super) fn new(inner: &'a mut dyn Read, code_page: CodePage) -> Self {
+        Self { inner, code_page }
+    }
+}
+
+impl Read for EbcdicReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        for byte in &mut buf[..count] {
+            *byte = self.code_page.decode(*byte);
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out its input in chunks of `size` bytes, to split CRLF pairs.
+    struct Chunked<'a> {
+        data: &'a [u8],
+        size: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = self.data.len().min(self.size).min(buf.len());
+            buf[..count].copy_from_slice(&self.data[..count]);
+            self.data = &self.data[count..];
+            Ok(count)
+        }
+    }
+
+    fn decode(data: &[u8], size: usize) -> Vec<u8> {
+        let mut input = Chunked { data, size };
+        let mut out = vec![];
+        AsciiReader::new(&mut input).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn encode_lf() {
+        let mut out = vec![];
+        let mut writer = AsciiWriter::new(&mut out);
+        writer.write_all(b"foo\nbar\n").unwrap();
+        writer.write_all(b"\nbaz\r\n").unwrap();
+        assert_eq!(out, b"foo\r\nbar\r\n\r\nbaz\r\r\n");
+    }
+
+    #[test]
+    fn decode_crlf() {
+        for size in [1, 2, 3, 1024] {
+            assert_eq!(decode(b"foo\r\nbar\r\n", size), b"foo\nbar\n");
+            assert_eq!(decode(b"a\rb\r\r\nc\r", size), b"a\rb\r\nc\r");
+            assert_eq!(decode(b"\r\r\r\n\n", size), b"\r\r\n\n");
+        }
+    }
+
+    #[test]
+    fn decode_into_small_buffer() {
+        let mut data: &[u8] = b"a\r\r\nb";
+        let mut reader = AsciiReader::new(&mut data);
+        let mut out = vec![];
+        let mut byte = [0];
+        while reader.read(&mut byte).unwrap() == 1 {
+            out.push(byte[0]);
+        }
+        assert_eq!(out, b"a\r\nb");
+    }
+
+    #[test]
+    fn round_trip() {
+        let text: Vec<u8> = (0..10_000u32)
+            .map(|i| b"ab\r\n\n"[i as usize % 5])
+            .collect();
+        let mut encoded = vec![];
+        AsciiWriter::new(&mut encoded).write_all(&text).unwrap();
+        assert_eq!(decode(&encoded, 7), text);
+    }
+
+    #[test]
+    fn ebcdic_text() {
+        let mut out = vec![];
+        EbcdicWriter::new(&mut out, CodePage::Cp037)
+            .write_all(b"Hi [1]\n")
+            .unwrap();
+        assert_eq!(out, [0xc8, 0x89, 0x40, 0xba, 0xf1, 0xbb, 0x15]);
+
+        let mut decoded = vec![];
+        EbcdicReader::new(&mut out.as_slice(), CodePage::Cp037)
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"Hi [1]\n");
+    }
+
+    #[test]
+    fn ebcdic_round_trip() {
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        for code_page in [CodePage::Cp037, CodePage::Cp1047] {
+            let mut encoded = vec![];
+            EbcdicWriter::new(&mut encoded, code_page)
+                .write_all(&data)
+                .unwrap();
+            assert_ne!(encoded, data);
+
+            let mut decoded = vec![];
+            let mut input = Chunked {
+                data: &encoded,
+                size: 4096,
+            };
+            EbcdicReader::new(&mut input, code_page)
+                .read_to_end(&mut decoded)
+                .unwrap();
+            assert_eq!(decoded, data);
+        }
+    }
+}
@@ -1,10 +1,18 @@
 use std::{
-    io::{Read, Write},
-    net::{IpAddr, Ipv4Addr, SocketAddrV4, TcpListener, TcpStream},
-    path::PathBuf,
+    fs::canonicalize,
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
 };
 
-use crate::{command::verb::Verb, config, status::Status};
+use crate::{
+    auth::{Identity, Permissions},
+    command::verb::Verb,
+    config,
+    status::Status,
+};
+
+use super::{ebcdic::CodePage, vfs};
 
 type TransferFunc = fn(
     parameter: &str,
@@ -13,39 +21,152 @@ type TransferFunc = fn(
     write_stream: Option<&mut dyn Write>,
 ) -> (Status, String);
 
+type SocketTransferFunc =
+    fn(parameter: &str, start_position: usize, socket: &TcpStream) -> (Status, String);
+
+type CleanupFunc = fn(parameter: &str);
+
+/// Transfer mode chosen with MODE.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum TransferMode {
+    Stream,
+    Block,
+    Deflate,
+}
+
+/// File structure chosen with STRU.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum FileStructure {
+    File,
+    /// Each line of a file is sent as one record.
+    Record,
+}
+
 pub(crate) struct SessionState {
     pub(crate) user: Option<String>,
     pub(crate) is_authenticated: bool,
+    pub(crate) identity: Option<Identity>,
     pub(crate) previous_command: Option<Verb>,
     pub(crate) binary_flag: bool,
+    /// Code page of TYPE E, text is sent as ASCII when not set.
+    pub(crate) ebcdic: Option<CodePage>,
+    pub(crate) transfer_mode: TransferMode,
+    pub(crate) structure: FileStructure,
+    /// Compression level for deflate mode, set with `OPTS MODE Z LEVEL`.
+    pub(crate) deflate_level: u32,
+    /// Real directory that is presented to the client as `/`.
+    pub(crate) root: PathBuf,
+    /// Virtual working directory, relative to `root`.
     pub(crate) name_prefix: PathBuf,
     pub(crate) has_greeted: bool,
     pub(crate) file_offset: usize,
+    pub(crate) rename_from: Option<PathBuf>,
+
+    /// Set by AUTH, the control connection is upgraded after the reply.
+    pub(crate) tls_pending: bool,
+    pub(crate) tls_active: bool,
+    /// Connected on the implicit FTPS port, data connections must use TLS.
+    pub(crate) implicit_tls: bool,
+    pub(crate) pbsz_set: bool,
+    /// Whether data connections are wrapped in TLS (PROT P).
+    pub(crate) protect_data: bool,
 
-    pub(crate) local_ip: Ipv4Addr,
-    pub(crate) peer_ip: Ipv4Addr,
+    pub(crate) local_ip: IpAddr,
+    pub(crate) peer_ip: IpAddr,
 
-    pub(crate) port_ip: Option<SocketAddrV4>,
+    pub(crate) port_ip: Option<SocketAddr>,
+    /// Set by `EPSV ALL`, only EPSV may set up data connections afterwards.
+    pub(crate) epsv_all: bool,
     pub(crate) data_listener: Option<TcpListener>,
 
     pub(crate) data_transfer_func: Option<TransferFunc>,
+    /// Used instead of `data_transfer_func` when the data connection is plain
+    /// TCP, for transfers that can hand the socket to the kernel.
+    pub(crate) data_socket_transfer_func: Option<SocketTransferFunc>,
     pub(crate) data_transfer_func_parameter: Option<String>,
-
-    pub(super) data_socket: Option<TcpStream>,
+    /// Called with the parameter when a transfer does not complete, also if
+    /// the data connection could not be opened at all.
+    pub(crate) data_transfer_cleanup: Option<CleanupFunc>,
+    /// Whether the transfer carries file contents, which are translated to
+    /// the representation type chosen with TYPE. Listings are always sent
+    /// as they are.
+    pub(crate) data_transfer_translated: bool,
+    /// Whether the client sends the data, so that the framing of block and
+    /// deflate mode and of record structure is ended by the client.
+    pub(crate) data_transfer_upload: bool,
 }
 
 impl SessionState {
     pub fn new(local_ip: IpAddr, peer_ip: IpAddr) -> Self {
-        let mut state = Self::default();
-        state.local_ip = match local_ip {
-            IpAddr::V4(ip) => ip,
-            _ => panic!("Only IPv4 is supported"),
-        };
-        state.peer_ip = match peer_ip {
-            IpAddr::V4(ip) => ip,
-            _ => panic!("Only IPv4 is supported"),
+        // IPv4 clients on a dual-stack listener show up as IPv4-mapped IPv6
+        // addresses, treat them as the IPv4 sessions they are.
+        Self {
+            local_ip: local_ip.to_canonical(),
+            peer_ip: peer_ip.to_canonical(),
+            ..Self::default()
+        }
+    }
+
+    /// Permissions of the logged in user, nothing is allowed before login.
+    pub fn permissions(&self) -> Permissions {
+        match (self.is_authenticated, &self.identity) {
+            (true, Some(identity)) => identity.permissions,
+            _ => Permissions::default(),
+        }
+    }
+
+    /// Whether the session may exchange data with `host` instead of its own
+    /// peer, for server to server (FXP) transfers.
+    pub fn fxp_allowed(&self, host: IpAddr) -> bool {
+        config::allow_fxp() || self.fxp_allowed_by(host, &config::fxp_allow_list())
+    }
+
+    /// Users with the `f` flag may always do FXP, others only if their name
+    /// or the other host is in `allow_list`.
+    fn fxp_allowed_by(&self, host: IpAddr, allow_list: &[String]) -> bool {
+        if self.permissions().fxp {
+            return true;
+        }
+
+        let user = match self.is_authenticated {
+            true => self.user.as_deref(),
+            false => None,
         };
-        state
+
+        allow_list
+            .iter()
+            .any(|entry| match entry.parse::<IpAddr>() {
+                Ok(address) => address == host.to_canonical(),
+                Err(_) => Some(entry.as_str()) == user,
+            })
+    }
+
+    /// Logs the session in as `identity`, confining it to its home directory.
+    pub fn log_in(&mut self, identity: Identity) {
+        self.is_authenticated = true;
+        self.root = canonicalize(&identity.home).unwrap_or_else(|_| identity.home.clone());
+        self.name_prefix = PathBuf::from(config::NAME_PREFIX);
+        self.identity = Some(identity);
+    }
+
+    /// Resolves a client supplied path to a real path inside the session root.
+    pub fn resolve_path(&self, argument: &str) -> io::Result<PathBuf> {
+        let decoded_argument = argument.replace('\0', "\n");
+        let virtual_path = vfs::virtual_join(&self.name_prefix, &decoded_argument);
+        vfs::resolve(&self.root, &virtual_path)
+    }
+
+    /// Resolves a client supplied path without following a symlink in its
+    /// last component.
+    pub fn resolve_entry(&self, argument: &str) -> io::Result<PathBuf> {
+        let decoded_argument = argument.replace('\0', "\n");
+        let virtual_path = vfs::virtual_join(&self.name_prefix, &decoded_argument);
+        vfs::resolve_entry(&self.root, &virtual_path)
+    }
+
+    /// The path the client sees for a real path inside the session root.
+    pub fn virtual_path(&self, real: &Path) -> PathBuf {
+        vfs::to_virtual(&self.root, real)
     }
 }
 
@@ -54,18 +175,34 @@ impl Default for SessionState {
         Self {
             user: None,
             is_authenticated: false,
+            identity: None,
             previous_command: None,
             binary_flag: false,
+            ebcdic: None,
+            transfer_mode: TransferMode::Stream,
+            structure: FileStructure::File,
+            deflate_level: config::DEFAULT_DEFLATE_LEVEL,
             has_greeted: false,
+            root: config::session_root_dir(),
             name_prefix: PathBuf::from(config::NAME_PREFIX),
             data_listener: None,
-            local_ip: Ipv4Addr::UNSPECIFIED,
-            peer_ip: Ipv4Addr::UNSPECIFIED,
+            local_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            peer_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
             port_ip: None,
+            epsv_all: false,
             data_transfer_func: None,
-            data_socket: None,
+            data_socket_transfer_func: None,
             file_offset: 0,
+            rename_from: None,
+            tls_pending: false,
+            tls_active: false,
+            implicit_tls: false,
+            pbsz_set: false,
+            protect_data: false,
             data_transfer_func_parameter: None,
+            data_transfer_cleanup: None,
+            data_transfer_translated: false,
+            data_transfer_upload: false,
         }
     }
 }
@@ -76,9 +213,15 @@ impl Clone for SessionState {
         Self {
             user: self.user.clone(),
             is_authenticated: self.is_authenticated,
+            identity: self.identity.clone(),
             previous_command: self.previous_command.clone(),
             binary_flag: self.binary_flag,
+            ebcdic: self.ebcdic,
+            transfer_mode: self.transfer_mode,
+            structure: self.structure,
+            deflate_level: self.deflate_level,
             has_greeted: self.has_greeted,
+            root: self.root.clone(),
             name_prefix: self.name_prefix.clone(),
             data_listener: match self.data_listener {
                 Some(ref listener) => Some(listener.try_clone().unwrap()),
@@ -87,36 +230,91 @@ impl Clone for SessionState {
             local_ip: self.local_ip.clone(),
             peer_ip: self.peer_ip.clone(),
             port_ip: self.port_ip.clone(),
+            epsv_all: self.epsv_all,
             data_transfer_func: self.data_transfer_func.clone(),
-            data_socket: match self.data_socket {
-                Some(ref socket) => Some(socket.try_clone().unwrap()),
-                None => None,
-            },
+            data_socket_transfer_func: self.data_socket_transfer_func,
             file_offset: self.file_offset,
+            rename_from: self.rename_from.clone(),
+            tls_pending: self.tls_pending,
+            tls_active: self.tls_active,
+            implicit_tls: self.implicit_tls,
+            pbsz_set: self.pbsz_set,
+            protect_data: self.protect_data,
             data_transfer_func_parameter: self.data_transfer_func_parameter.clone(),
+            data_transfer_cleanup: self.data_transfer_cleanup,
+            data_transfer_translated: self.data_transfer_translated,
+            data_transfer_upload: self.data_transfer_upload,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::net::Ipv6Addr;
+    use std::{fs, net::Ipv6Addr};
+
+    use crate::testing::test_dir;
 
     use super::*;
 
     #[test]
-    #[should_panic]
-    fn ipv6_should_panic() {
-        let addr = IpAddr::V6(Ipv6Addr::LOCALHOST);
-        let ok = IpAddr::V4(Ipv4Addr::LOCALHOST);
-        let _ = SessionState::new(addr, ok);
+    fn resolve_relative_to_root() {
+        let root = test_dir("state", "root");
+        fs::create_dir(root.join("pub")).unwrap();
+
+        let state = SessionState {
+            root: root.clone(),
+            name_prefix: PathBuf::from("/pub"),
+            ..SessionState::default()
+        };
+        assert_eq!(state.resolve_path("..").unwrap(), root);
+        assert_eq!(state.resolve_path("/../..").unwrap(), root);
+        assert_eq!(state.resolve_path("a\0b").unwrap(), root.join("pub/a\nb"));
+        assert_eq!(state.virtual_path(&root.join("pub")), Path::new("/pub"));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn fxp_policy() {
+        let host: IpAddr = "192.0.2.1".parse().unwrap();
+        let mut state = SessionState {
+            user: Some("mirror".to_string()),
+            ..SessionState::default()
+        };
+        let list = ["192.0.2.1".to_string(), "mirror".to_string()];
+
+        assert!(!state.fxp_allowed_by(host, &[]));
+        assert!(state.fxp_allowed_by(host, &list[..1]));
+        assert!(!state.fxp_allowed_by("192.0.2.2".parse().unwrap(), &list[..1]));
+
+        // User names only count once logged in.
+        assert!(!state.fxp_allowed_by("192.0.2.2".parse().unwrap(), &list[1..]));
+        state.log_in(Identity {
+            display_name: "mirror".to_string(),
+            home: PathBuf::from("/"),
+            permissions: Permissions::from_flags("r"),
+        });
+        assert!(state.fxp_allowed_by("192.0.2.2".parse().unwrap(), &list[1..]));
+
+        state.user = Some("other".to_string());
+        assert!(!state.fxp_allowed_by(host, &list[1..]));
+        state.identity.as_mut().unwrap().permissions = Permissions::from_flags("rf");
+        assert!(state.fxp_allowed_by(host, &[]));
+    }
+
+    #[test]
+    fn ipv6_session() {
+        let local = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let peer = IpAddr::V6("2001:db8::1".parse().unwrap());
+        let state = SessionState::new(local, peer);
+        assert_eq!(state.local_ip, local);
+        assert_eq!(state.peer_ip, peer);
     }
 
     #[test]
-    #[should_panic]
-    fn ipv6_peer_should_panic() {
-        let addr = IpAddr::V6(Ipv6Addr::LOCALHOST);
-        let ok = IpAddr::V4(Ipv4Addr::LOCALHOST);
-        let _ = SessionState::new(ok, addr);
+    fn ipv4_mapped_session() {
+        let mapped = IpAddr::V6(Ipv4Addr::LOCALHOST.to_ipv6_mapped());
+        let state = SessionState::new(mapped, mapped);
+        assert_eq!(state.local_ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(state.peer_ip, IpAddr::V4(Ipv4Addr::LOCALHOST));
     }
 }
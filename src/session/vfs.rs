@@ -0,0 +1,168 @@
+use std::{
+    fs::canonicalize,
+    io::{self, ErrorKind},
+    path::{Component, Path, PathBuf},
+};
+
+/// Joins a client supplied path onto the virtual working directory. `..` can
+/// never climb above the virtual root.
+pub(super) fn virtual_join(cwd: &Path, argument: &str) -> PathBuf {
+    let mut result = PathBuf::from("/");
+
+    for component in cwd.join(argument).components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => result = PathBuf::from("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            Component::Normal(name) => result.push(name),
+        }
+    }
+
+    result
+}
+
+/// Maps a virtual path onto the real file system, following symlinks. Paths
+/// that end up outside of `root` are refused. The last component does not
+/// have to exist, as long as its parent does.
+pub(super) fn resolve(root: &Path, virtual_path: &Path) -> io::Result<PathBuf> {
+    let relative = virtual_path.strip_prefix("/").unwrap_or(virtual_path);
+    let path = root.join(relative);
+
+    let real = match path.symlink_metadata() {
+        Ok(_) => canonicalize(&path)?,
+        Err(ref error) if error.kind() == ErrorKind::NotFound => {
+            let name = match path.file_name() {
+                Some(name) => name,
+                None => return Err(error_outside_root()),
+            };
+            canonicalize(path.parent().unwrap_or(root))?.join(name)
+        }
+        Err(error) => return Err(error),
+    };
+
+    match real.starts_with(root) {
+        true => Ok(real),
+        false => Err(error_outside_root()),
+    }
+}
+
+/// Like `resolve`, but does not follow a symlink in the last component, so
+/// that the entry itself can be removed or renamed.
+pub(super) fn resolve_entry(root: &Path, virtual_path: &Path) -> io::Result<PathBuf> {
+    let name = match virtual_path.file_name() {
+        Some(name) => name,
+        None => return Ok(root.to_path_buf()),
+    };
+    let parent = resolve(root, virtual_path.parent().unwrap_or(Path::new("/")))?;
+    Ok(parent.join(name))
+}
+
+/// Maps a real path inside `root` back to the path the client sees.
+pub(super) fn to_virtual(root: &Path, real: &Path) -> PathBuf {
+    match real.strip_prefix(root) {
+        Ok(relative) => Path::new("/").join(relative),
+        Err(_) => PathBuf::from("/"),
+    }
+}
+
+fn error_outside_root() -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, "Permission denied")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::symlink};
+
+    use crate::testing::test_dir;
+
+    use super::*;
+
+    /// A directory holding a `root` to serve and a `secret` outside of it.
+    fn test_root(name: &str) -> PathBuf {
+        let dir = test_dir("vfs", name);
+        fs::create_dir_all(dir.join("root").join("pub")).unwrap();
+        fs::create_dir(dir.join("secret")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn join_stays_below_root() {
+        let cwd = Path::new("/pub");
+        assert_eq!(virtual_join(cwd, ""), Path::new("/pub"));
+        assert_eq!(virtual_join(cwd, "foo/./bar"), Path::new("/pub/foo/bar"));
+        assert_eq!(virtual_join(cwd, "../../../etc"), Path::new("/etc"));
+        assert_eq!(virtual_join(cwd, "/etc/../usr"), Path::new("/usr"));
+        assert_eq!(virtual_join(cwd, ".."), Path::new("/"));
+    }
+
+    #[test]
+    fn resolve_inside_root() {
+        let dir = test_root("inside");
+        let root = dir.join("root");
+        assert_eq!(resolve(&root, Path::new("/")).unwrap(), root);
+        assert_eq!(resolve(&root, Path::new("/pub")).unwrap(), root.join("pub"));
+        assert_eq!(
+            resolve(&root, Path::new("/pub/new.txt")).unwrap(),
+            root.join("pub").join("new.txt")
+        );
+        assert_eq!(
+            resolve(&root, Path::new("/missing/new.txt"))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::NotFound
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn symlinks_cannot_escape() {
+        let dir = test_root("symlink");
+        let root = dir.join("root");
+        symlink(dir.join("secret"), root.join("escape")).unwrap();
+        symlink(dir.join("secret").join("nope"), root.join("dangling")).unwrap();
+        symlink(root.join("pub"), root.join("inside")).unwrap();
+
+        for path in ["/escape", "/escape/file.txt"] {
+            let error = resolve(&root, Path::new(path)).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::PermissionDenied);
+        }
+        assert!(resolve(&root, Path::new("/dangling")).is_err());
+        assert_eq!(
+            resolve(&root, Path::new("/inside")).unwrap(),
+            root.join("pub")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn entry_does_not_follow_last_symlink() {
+        let dir = test_root("entry");
+        let root = dir.join("root");
+        symlink(dir.join("secret"), root.join("escape")).unwrap();
+        assert_eq!(
+            resolve_entry(&root, Path::new("/escape")).unwrap(),
+            root.join("escape")
+        );
+        assert_eq!(resolve_entry(&root, Path::new("/")).unwrap(), root);
+        assert_eq!(
+            resolve_entry(&root, Path::new("/escape/file.txt"))
+                .unwrap_err()
+                .kind(),
+            ErrorKind::PermissionDenied
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn virtual_from_real() {
+        let root = Path::new("/srv/ftp");
+        assert_eq!(to_virtual(root, Path::new("/srv/ftp")), Path::new("/"));
+        assert_eq!(
+            to_virtual(root, Path::new("/srv/ftp/pub/a")),
+            Path::new("/pub/a")
+        );
+        assert_eq!(to_virtual(root, Path::new("/etc")), Path::new("/"));
+    }
+}
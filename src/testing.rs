@@ -0,0 +1,10 @@
+use std::{env, fs, path::PathBuf, process};
+
+/// Creates an empty directory for the test `name` of `module`, unique to this
+/// test run.
+pub(crate) fn test_dir(module: &str, name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("fetp-{}-{}-{}", module, name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::canonicalize(dir).unwrap()
+}
@@ -0,0 +1,190 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, ErrorKind, Read, Write},
+    net::TcpStream,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+use log::{error, info};
+use rustls::{crypto::ring, ServerConfig, ServerConnection, StreamOwned};
+
+use crate::config;
+
+lazy_static! {
+    static ref SERVER_CONFIG: Option<Arc<ServerConfig>> = create_server_config();
+}
+
+/// The TLS configuration for this server, if a certificate and key are set.
+pub(crate) fn server_config() -> Option<Arc<ServerConfig>> {
+    SERVER_CONFIG.clone()
+}
+
+fn create_server_config() -> Option<Arc<ServerConfig>> {
+    let (cert, key) = match (config::tls_cert_file(), config::tls_key_file()) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => return None,
+    };
+
+    match load_server_config(&cert, &key) {
+        Ok(config) => {
+            info!("Loaded TLS certificate from {}", cert.display());
+            Some(Arc::new(config))
+        }
+        Err(e) => {
+            error!("Failed to load TLS certificate: {}", e);
+            None
+        }
+    }
+}
+
+/// Builds a server configuration from a PEM certificate chain and private key.
+pub(crate) fn load_server_config(cert: &Path, key: &Path) -> io::Result<ServerConfig> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "No certificate found",
+        ));
+    }
+
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key)?))?
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "No private key found"))?;
+
+    ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Server side of a TLS connection. Clones share the same connection, so one
+/// can be used for reading and another for writing.
+#[derive(Clone)]
+pub(crate) struct TlsStream {
+    inner: Arc<Mutex<StreamOwned<ServerConnection, TcpStream>>>,
+}
+
+impl TlsStream {
+    /// Performs the server handshake on `socket`.
+    pub(crate) fn accept(config: Arc<ServerConfig>, socket: TcpStream) -> io::Result<TlsStream> {
+        let connection = ServerConnection::new(config).map_err(io::Error::other)?;
+        let mut stream = StreamOwned::new(connection, socket);
+
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+
+        Ok(TlsStream {
+            inner: Arc::new(Mutex::new(stream)),
+        })
+    }
+
+    /// Tells the peer that no more data will be sent.
+    pub(crate) fn close(&self) -> io::Result<()> {
+        let mut stream = self.inner.lock().unwrap();
+        stream.conn.send_close_notify();
+        stream.flush()
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.lock().unwrap().read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.lock().unwrap().flush()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{net::TcpListener, path::PathBuf, thread};
+
+    use rustls::{
+        pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned,
+    };
+
+    use super::*;
+
+    pub(crate) fn test_cert() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("res/test/cert.pem")
+    }
+
+    fn test_ca() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("res/test/ca.pem")
+    }
+
+    pub(crate) fn test_key() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("res/test/key.pem")
+    }
+
+    /// A TLS client on `socket` that trusts the test CA.
+    pub(crate) fn test_client(socket: TcpStream) -> StreamOwned<ClientConnection, TcpStream> {
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut BufReader::new(File::open(test_ca()).unwrap())) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let name = ServerName::try_from("localhost").unwrap();
+        let connection = ClientConnection::new(Arc::new(config), name).unwrap();
+        StreamOwned::new(connection, socket)
+    }
+
+    #[test]
+    fn load_pem_files() {
+        assert!(load_server_config(&test_cert(), &test_key()).is_ok());
+    }
+
+    #[test]
+    fn load_invalid_files() {
+        let missing = Path::new("/lajsldf/cert.pem");
+        assert!(load_server_config(missing, &test_key()).is_err());
+        assert!(load_server_config(&test_cert(), missing).is_err());
+
+        let error = load_server_config(&test_key(), &test_key()).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        let error = load_server_config(&test_cert(), &test_cert())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn handshake_and_exchange() {
+        let config = Arc::new(load_server_config(&test_cert(), &test_key()).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let mut client = test_client(TcpStream::connect(address).unwrap());
+            client.write_all(b"ping").unwrap();
+            let mut buf = [0; 4];
+            client.read_exact(&mut buf).unwrap();
+            buf
+        });
+
+        let (socket, _) = listener.accept().unwrap();
+        let mut server = TlsStream::accept(config, socket).unwrap();
+        let mut buf = [0; 4];
+        server.clone().read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        server.write_all(b"pong").unwrap();
+        server.close().unwrap();
+
+        assert_eq!(&client.join().unwrap(), b"pong");
+    }
+}